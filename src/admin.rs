@@ -0,0 +1,695 @@
+//! Client of the dashboard API of the Gorse master node, for operations:
+//! cluster members, tasks, data statistics, feedback rates and the current
+//! configuration.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), gorse_rs::Error> {
+//! use gorse_rs::admin::GorseAdmin;
+//!
+//! let admin = GorseAdmin::new("http://127.0.0.1:8088");
+//! admin.login("admin", "password").await?;
+//! for node in admin.cluster().await? {
+//!     println!("{} {} {}", node.node_type, node.hostname, node.version);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The data of a cluster can be backed up to a file and restored, e.g. in
+//! another environment:
+//!
+//! ```no_run
+//! # async fn run(admin: gorse_rs::admin::GorseAdmin) -> Result<(), gorse_rs::Error> {
+//! let mut file = tokio::fs::File::create("gorse.dump").await?;
+//! admin.dump_to(&mut file, |progress| println!("{} bytes", progress.bytes)).await?;
+//!
+//! let file = tokio::fs::File::open("gorse.dump").await?;
+//! let stats = admin.restore_from(file, |_| {}).await?;
+//! println!("{} users, {} items, {} feedback", stats.users, stats.items, stats.feedback);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+
+use reqwest::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE};
+use reqwest::redirect::Policy;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio_util::io::ReaderStream;
+
+use crate::response::{self, BodyRecord, Decode};
+use crate::{Error, Result};
+
+/// Node of a Gorse cluster.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClusterNode {
+    #[serde(rename = "UUID", default)]
+    pub uuid: String,
+    #[serde(rename = "Hostname", default)]
+    pub hostname: String,
+    /// Role of the node: `master`, `server` or `worker`.
+    #[serde(rename = "Type", default)]
+    pub node_type: String,
+    #[serde(rename = "Version", default)]
+    pub version: String,
+    /// Time of the last heartbeat of the node.
+    #[serde(rename = "UpdateTime", default)]
+    pub update_time: String,
+}
+
+/// Task of the master node, e.g. loading the dataset or training a model.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Task {
+    #[serde(rename = "Tracer", default)]
+    pub tracer: String,
+    #[serde(rename = "Name")]
+    pub name: String,
+    /// Status of the task, e.g. `Running` or `Complete`.
+    #[serde(rename = "Status", default)]
+    pub status: String,
+    #[serde(rename = "Error", default)]
+    pub error: String,
+    /// Number of steps done, out of `total`.
+    #[serde(rename = "Count", default)]
+    pub count: u64,
+    #[serde(rename = "Total", default)]
+    pub total: u64,
+    #[serde(rename = "StartTime", default)]
+    pub start_time: String,
+    #[serde(rename = "FinishTime", default)]
+    pub finish_time: String,
+}
+
+/// Statistics of the data of a Gorse cluster.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    #[serde(rename = "NumUsers")]
+    pub num_users: u64,
+    #[serde(rename = "NumItems")]
+    pub num_items: u64,
+    #[serde(rename = "NumTotalPosFeedback")]
+    pub num_total_pos_feedback: u64,
+    #[serde(rename = "NumValidPosFeedback")]
+    pub num_valid_pos_feedback: u64,
+    #[serde(rename = "NumValidNegFeedback")]
+    pub num_valid_neg_feedback: u64,
+    /// Version of the master node.
+    #[serde(rename = "BinaryVersion")]
+    pub binary_version: String,
+}
+
+/// Point of a time series, e.g. of the rate of a feedback type.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RatePoint {
+    #[serde(rename = "Timestamp")]
+    pub timestamp: String,
+    #[serde(rename = "Value")]
+    pub value: f64,
+}
+
+/// Number of records restored by [`GorseAdmin::restore_from`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RestoreStats {
+    #[serde(rename = "Users")]
+    pub users: u64,
+    #[serde(rename = "Items")]
+    pub items: u64,
+    #[serde(rename = "Feedback")]
+    pub feedback: u64,
+}
+
+/// Progress of a dump or restore, reported after each chunk of data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// Bytes transferred so far.
+    pub bytes: u64,
+    /// Size of the dump, if announced by the server.
+    pub total_bytes: Option<u64>,
+}
+
+impl Decode for ClusterNode {}
+impl Decode for Task {}
+impl Decode for Stats {}
+impl Decode for RatePoint {}
+impl Decode for RestoreStats {}
+
+/// Dashboard credentials and session cookie, shared by the clones of a
+/// client.
+#[derive(Default)]
+struct Session {
+    credentials: Option<(String, String)>,
+    cookie: Option<HeaderValue>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field(
+                "user_name",
+                &self.credentials.as_ref().map(|(user_name, _)| user_name),
+            )
+            .field("cookie", &self.cookie.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl Session {
+    fn credentials(&self) -> Option<(String, String)> {
+        self.credentials.clone()
+    }
+}
+
+/// Client of the dashboard API of the Gorse master node.
+#[derive(Debug, Clone)]
+pub struct GorseAdmin {
+    entry_point: String,
+    client: reqwest::Client,
+    session: Arc<Mutex<Session>>,
+}
+
+impl GorseAdmin {
+    /// Creates a client of the master node at `entry_point`, e.g.
+    /// `http://127.0.0.1:8088`. Dashboards with a user must be logged in
+    /// with [`login`](Self::login).
+    pub fn new(entry_point: impl Into<String>) -> Self {
+        Self {
+            entry_point: entry_point.into(),
+            client: reqwest::Client::builder()
+                .redirect(Policy::none())
+                .build()
+                .expect("the admin client uses no TLS settings"),
+            session: Arc::default(),
+        }
+    }
+
+    /// Logs in with the dashboard user name and password. The session
+    /// cookie is sent with the following requests, and the client logs in
+    /// again when the session expires.
+    pub async fn login(&self, user_name: &str, password: &str) -> Result<()> {
+        let response = self
+            .client
+            .post(url(&self.entry_point, "login"))
+            .form(&[("user_name", user_name), ("password", password)])
+            .send()
+            .await?;
+        let cookie = session_cookie(response.status(), response.headers())?;
+        let mut session = self.session.lock().unwrap();
+        session.credentials = Some((user_name.into(), password.into()));
+        session.cookie = Some(cookie);
+        Ok(())
+    }
+
+    /// Lists the nodes of the cluster.
+    pub async fn cluster(&self) -> Result<Vec<ClusterNode>> {
+        self.get_json("api/dashboard/cluster").await
+    }
+
+    /// Lists the tasks of the master node with their progress.
+    pub async fn tasks(&self) -> Result<Vec<Task>> {
+        self.get_json("api/dashboard/tasks").await
+    }
+
+    /// Returns statistics of the users, items and feedback.
+    pub async fn stats(&self) -> Result<Stats> {
+        self.get_json("api/dashboard/stats").await
+    }
+
+    /// Returns the recent rates of each positive feedback type.
+    pub async fn rates(&self) -> Result<HashMap<String, Vec<RatePoint>>> {
+        self.get_json("api/dashboard/rates").await
+    }
+
+    /// Returns the configuration of the cluster, as JSON.
+    pub async fn config(&self) -> Result<Value> {
+        self.get_json("api/dashboard/config").await
+    }
+
+    /// Writes a dump of the users, items and feedback of the cluster.
+    pub async fn dump_to<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        mut progress: impl FnMut(TransferProgress),
+    ) -> Result<TransferProgress> {
+        let mut response = self.get("api/dump").await?;
+        let mut transferred = TransferProgress {
+            bytes: 0,
+            total_bytes: response.content_length(),
+        };
+        while let Some(chunk) = response.chunk().await? {
+            writer.write_all(&chunk).await?;
+            transferred.bytes += chunk.len() as u64;
+            progress(transferred);
+        }
+        writer.flush().await?;
+        Ok(transferred)
+    }
+
+    /// Restores a dump written by [`dump_to`](Self::dump_to), streaming it
+    /// from `reader`. The request is not retried if the session expired, since
+    /// the reader is consumed: log in before restoring large dumps.
+    pub async fn restore_from<R: AsyncRead + Unpin + Send + 'static>(
+        &self,
+        reader: R,
+        progress: impl FnMut(TransferProgress) + Send + 'static,
+    ) -> Result<RestoreStats> {
+        let body =
+            reqwest::Body::wrap_stream(ReaderStream::new(ProgressReader::new(reader, progress)));
+        let request = self
+            .client
+            .post(url(&self.entry_point, "api/restore"))
+            .body(body);
+        let response = check_status(self.send(request).await?).await?;
+        decode(response).await
+    }
+
+    async fn get_json<T: Decode>(&self, path: &str) -> Result<T> {
+        decode(self.get(path).await?).await
+    }
+
+    async fn get(&self, path: &str) -> Result<Response> {
+        let request = || self.client.get(url(&self.entry_point, path));
+        let mut response = self.send(request()).await?;
+        if is_logged_out(response.status()) {
+            let credentials = self.session.lock().unwrap().credentials();
+            if let Some((user_name, password)) = credentials {
+                self.login(&user_name, &password).await?;
+                response = self.send(request()).await?;
+            }
+        }
+        check_status(response).await
+    }
+
+    async fn send(&self, mut request: RequestBuilder) -> Result<Response> {
+        if let Some(cookie) = self.session.lock().unwrap().cookie.clone() {
+            request = request.header(COOKIE, cookie);
+        }
+        Ok(request.send().await?)
+    }
+}
+
+async fn check_status(response: Response) -> Result<Response> {
+    let status_code = response.status();
+    if status_code.is_success() {
+        Ok(response)
+    } else if is_logged_out(status_code) {
+        Err(logged_out())
+    } else {
+        Err(Error::Api {
+            status_code,
+            message: response.text().await?,
+        })
+    }
+}
+
+async fn decode<T: Decode>(response: Response) -> Result<T> {
+    response::read_body(response, &mut BodyRecord::default()).await
+}
+
+fn url(entry_point: &str, path: &str) -> String {
+    format!("{}/{}", entry_point.trim_end_matches('/'), path)
+}
+
+/// Whether the dashboard rejected a request for a missing or expired
+/// session, which it does by redirecting to the login page.
+fn is_logged_out(status_code: StatusCode) -> bool {
+    status_code == StatusCode::UNAUTHORIZED || status_code.is_redirection()
+}
+
+fn logged_out() -> Error {
+    Error::Api {
+        status_code: StatusCode::UNAUTHORIZED,
+        message: "not logged in to the dashboard".into(),
+    }
+}
+
+/// Reader reporting the progress of a restore.
+struct ProgressReader<R> {
+    inner: R,
+    progress: Box<dyn FnMut(TransferProgress) + Send>,
+    transferred: TransferProgress,
+}
+
+impl<R> ProgressReader<R> {
+    fn new(inner: R, progress: impl FnMut(TransferProgress) + Send + 'static) -> Self {
+        Self {
+            inner,
+            progress: Box::new(progress),
+            transferred: TransferProgress::default(),
+        }
+    }
+
+    fn record(&mut self, n: usize) {
+        if n > 0 {
+            self.transferred.bytes += n as u64;
+            (self.progress)(self.transferred);
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ProgressReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.record(buf.filled().len() - filled);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<R: io::Read> io::Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.record(n);
+        Ok(n)
+    }
+}
+
+/// Extracts the session cookie set by a login response. The dashboard
+/// redirects both successful and failed logins, and only sets a cookie on
+/// success.
+fn session_cookie(status_code: StatusCode, headers: &HeaderMap) -> Result<HeaderValue> {
+    if status_code.is_client_error() || status_code.is_server_error() {
+        return Err(Error::Api {
+            status_code,
+            message: "login failed".into(),
+        });
+    }
+    let cookies: Vec<&str> = headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok())
+        .filter_map(|cookie| cookie.split(';').next())
+        .map(str::trim)
+        .filter(|cookie| !cookie.is_empty())
+        .collect();
+    if cookies.is_empty() {
+        return Err(Error::Api {
+            status_code: StatusCode::UNAUTHORIZED,
+            message: "incorrect user name or password".into(),
+        });
+    }
+    HeaderValue::from_str(&cookies.join("; "))
+        .map_err(|_| Error::Config("invalid session cookie".into()))
+}
+
+#[cfg(feature = "blocking")]
+pub mod blocking {
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    use reqwest::blocking::{Body, RequestBuilder, Response};
+    use reqwest::header::COOKIE;
+    use reqwest::redirect::Policy;
+    use serde_json::Value;
+
+    use super::{
+        is_logged_out, logged_out, session_cookie, url, ClusterNode, ProgressReader, RatePoint,
+        RestoreStats, Session, Stats, Task, TransferProgress,
+    };
+    use crate::response::{self, BodyRecord, Decode};
+    use crate::{Error, Result};
+
+    /// Blocking client of the dashboard API of the Gorse master node, see
+    /// [`admin::GorseAdmin`](super::GorseAdmin).
+    #[derive(Debug, Clone)]
+    pub struct GorseAdmin {
+        entry_point: String,
+        client: reqwest::blocking::Client,
+        session: Arc<Mutex<Session>>,
+    }
+
+    impl GorseAdmin {
+        pub fn new(entry_point: impl Into<String>) -> Self {
+            Self {
+                entry_point: entry_point.into(),
+                client: reqwest::blocking::Client::builder()
+                    .redirect(Policy::none())
+                    .build()
+                    .expect("the admin client uses no TLS settings"),
+                session: Arc::default(),
+            }
+        }
+
+        /// Logs in with the dashboard user name and password. The session
+        /// cookie is sent with the following requests, and the client logs in
+        /// again when the session expires.
+        pub fn login(&self, user_name: &str, password: &str) -> Result<()> {
+            let response = self
+                .client
+                .post(url(&self.entry_point, "login"))
+                .form(&[("user_name", user_name), ("password", password)])
+                .send()?;
+            let cookie = session_cookie(response.status(), response.headers())?;
+            let mut session = self.session.lock().unwrap();
+            session.credentials = Some((user_name.into(), password.into()));
+            session.cookie = Some(cookie);
+            Ok(())
+        }
+
+        /// Lists the nodes of the cluster.
+        pub fn cluster(&self) -> Result<Vec<ClusterNode>> {
+            self.get_json("api/dashboard/cluster")
+        }
+
+        /// Lists the tasks of the master node with their progress.
+        pub fn tasks(&self) -> Result<Vec<Task>> {
+            self.get_json("api/dashboard/tasks")
+        }
+
+        /// Returns statistics of the users, items and feedback.
+        pub fn stats(&self) -> Result<Stats> {
+            self.get_json("api/dashboard/stats")
+        }
+
+        /// Returns the recent rates of each positive feedback type.
+        pub fn rates(&self) -> Result<HashMap<String, Vec<RatePoint>>> {
+            self.get_json("api/dashboard/rates")
+        }
+
+        /// Returns the configuration of the cluster, as JSON.
+        pub fn config(&self) -> Result<Value> {
+            self.get_json("api/dashboard/config")
+        }
+
+        /// Writes a dump of the users, items and feedback of the cluster.
+        pub fn dump_to<W: Write>(
+            &self,
+            writer: &mut W,
+            mut progress: impl FnMut(TransferProgress),
+        ) -> Result<TransferProgress> {
+            let mut response = self.get("api/dump")?;
+            let mut transferred = TransferProgress {
+                bytes: 0,
+                total_bytes: response.content_length(),
+            };
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let n = response.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+                writer.write_all(&buffer[..n])?;
+                transferred.bytes += n as u64;
+                progress(transferred);
+            }
+            writer.flush()?;
+            Ok(transferred)
+        }
+
+        /// Restores a dump written by [`dump_to`](Self::dump_to), streaming it
+        /// from `reader`. The request is not retried if the session expired,
+        /// since the reader is consumed: log in before restoring large dumps.
+        pub fn restore_from<R: Read + Send + 'static>(
+            &self,
+            reader: R,
+            progress: impl FnMut(TransferProgress) + Send + 'static,
+        ) -> Result<RestoreStats> {
+            let body = Body::new(ProgressReader::new(reader, progress));
+            let request = self
+                .client
+                .post(url(&self.entry_point, "api/restore"))
+                .body(body);
+            let response = check_status(self.send(request)?)?;
+            decode(response)
+        }
+
+        fn get_json<T: Decode>(&self, path: &str) -> Result<T> {
+            decode(self.get(path)?)
+        }
+
+        fn get(&self, path: &str) -> Result<Response> {
+            let request = || self.client.get(url(&self.entry_point, path));
+            let mut response = self.send(request())?;
+            if is_logged_out(response.status()) {
+                let credentials = self.session.lock().unwrap().credentials();
+                if let Some((user_name, password)) = credentials {
+                    self.login(&user_name, &password)?;
+                    response = self.send(request())?;
+                }
+            }
+            check_status(response)
+        }
+
+        fn send(&self, mut request: RequestBuilder) -> Result<Response> {
+            if let Some(cookie) = self.session.lock().unwrap().cookie.clone() {
+                request = request.header(COOKIE, cookie);
+            }
+            Ok(request.send()?)
+        }
+    }
+
+    fn check_status(response: Response) -> Result<Response> {
+        let status_code = response.status();
+        if status_code.is_success() {
+            Ok(response)
+        } else if is_logged_out(status_code) {
+            Err(logged_out())
+        } else {
+            Err(Error::Api {
+                status_code,
+                message: response.text()?,
+            })
+        }
+    }
+
+    fn decode<T: Decode>(response: Response) -> Result<T> {
+        response::read_body_blocking(response, &mut BodyRecord::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{json, response, serve};
+    use std::thread::JoinHandle;
+
+    /// Serves a dashboard expecting a login before each API request, as if
+    /// sessions expired immediately.
+    fn serve_dashboard(requests: usize) -> (String, JoinHandle<Vec<String>>) {
+        let mut logged_in = false;
+        serve(requests, move |head, body| {
+            if head.starts_with("post /login") {
+                logged_in = body == b"user_name=admin&password=secret";
+                let location = if logged_in {
+                    "/\r\nSet-Cookie: session=abc; Path=/"
+                } else {
+                    "/login?msg=incorrect"
+                };
+                format!("HTTP/1.1 302 Found\r\nLocation: {}\r\n\r\n", location)
+            } else if logged_in && head.contains("cookie: session=abc") {
+                logged_in = false;
+                json(r#"[{"UUID":"1","Hostname":"node","Type":"worker","Version":"v0.5.0"}]"#)
+            } else {
+                response("401 Unauthorized", "text/plain", "")
+            }
+        })
+    }
+
+    /// Serves a dump and accepts the restore of the same dump.
+    fn serve_dump(dump: &'static [u8]) -> (String, JoinHandle<Vec<String>>) {
+        serve(2, move |head, body| {
+            if head.starts_with("get /api/dump") {
+                response(
+                    "200 OK",
+                    "application/octet-stream",
+                    &String::from_utf8_lossy(dump),
+                )
+            } else if body == dump {
+                json(r#"{"Users":1,"Items":2,"Feedback":3}"#)
+            } else {
+                response("400 Bad Request", "text/plain", "")
+            }
+        })
+    }
+
+    const DUMP: &[u8] = b"users items feedback";
+
+    #[tokio::test]
+    async fn test_admin() -> Result<()> {
+        let (entry_point, server) = serve_dashboard(6);
+        let admin = GorseAdmin::new(entry_point);
+        assert!(matches!(
+            admin.login("admin", "wrong").await,
+            Err(Error::Api {
+                status_code: StatusCode::UNAUTHORIZED,
+                ..
+            })
+        ));
+        admin.login("admin", "secret").await?;
+        let nodes = admin.cluster().await?;
+        assert_eq!(nodes[0].node_type, "worker");
+        // The session expired: the client logs in again.
+        admin.cluster().await?;
+        assert!(!format!("{:?}", admin).contains("secret"));
+        assert_eq!(
+            server.join().unwrap(),
+            vec![
+                "post /login http/1.1",
+                "post /login http/1.1",
+                "get /api/dashboard/cluster http/1.1",
+                "get /api/dashboard/cluster http/1.1",
+                "post /login http/1.1",
+                "get /api/dashboard/cluster http/1.1",
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dump_restore() -> Result<()> {
+        let (entry_point, server) = serve_dump(DUMP);
+        let admin = GorseAdmin::new(entry_point);
+        let mut dump = Vec::new();
+        let mut reported = TransferProgress::default();
+        let progress = admin
+            .dump_to(&mut dump, |progress| reported = progress)
+            .await?;
+        assert_eq!(dump, DUMP);
+        assert_eq!(progress.bytes, DUMP.len() as u64);
+        assert_eq!(progress.total_bytes, Some(DUMP.len() as u64));
+        assert_eq!(reported, progress);
+
+        let stats = admin
+            .restore_from(std::io::Cursor::new(dump), |_| {})
+            .await?;
+        assert_eq!((stats.users, stats.items, stats.feedback), (1, 2, 3));
+        server.join().unwrap();
+        Ok(())
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_blocking_admin() -> Result<()> {
+        let (entry_point, server) = serve_dashboard(2);
+        let admin = blocking::GorseAdmin::new(entry_point);
+        admin.login("admin", "secret")?;
+        let nodes = admin.cluster()?;
+        assert_eq!(nodes[0].hostname, "node");
+        server.join().unwrap();
+
+        let (entry_point, server) = serve_dump(DUMP);
+        let admin = blocking::GorseAdmin::new(entry_point);
+        let mut dump = Vec::new();
+        admin.dump_to(&mut dump, |_| {})?;
+        assert_eq!(dump, DUMP);
+        let stats = admin.restore_from(std::io::Cursor::new(dump), |_| {})?;
+        assert_eq!(stats.feedback, 3);
+        server.join().unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,616 @@
+//! Command-line client of the Gorse API.
+//!
+//! ```text
+//! gorse --entry-point http://127.0.0.1:8087 user get bob
+//! gorse recommend bob -n 10 --output json
+//! gorse import feedback feedback.csv
+//! ```
+//!
+//! The client is configured by the `GORSE_*` environment variables of
+//! `GorseConfig::from_env`, or by a configuration file, and flags override
+//! both.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use gorse_rs::blocking::Gorse;
+use gorse_rs::io::{CsvOptions, Format, Record};
+use gorse_rs::{
+    ApiKey, Error, Feedback, GorseConfig, HealthStatus, Item, RecommendOptions, RowAffected, Score,
+    User,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+#[cfg(test)]
+#[path = "../test_server.rs"]
+mod test_server;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "gorse",
+    version,
+    about = "Command-line client of the Gorse API"
+)]
+struct Cli {
+    /// Configuration file in TOML, JSON or YAML, instead of the `GORSE_*`
+    /// environment variables.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Gorse server endpoint.
+    #[arg(long, global = true)]
+    entry_point: Option<String>,
+    /// API key of the Gorse server.
+    #[arg(long, global = true)]
+    api_key: Option<String>,
+    #[arg(long, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Insert, get or delete a user.
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Insert, get or delete an item.
+    #[command(subcommand)]
+    Item(ItemCommand),
+    /// Insert or list feedback.
+    #[command(subcommand)]
+    Feedback(FeedbackCommand),
+    /// Recommend items to a user.
+    Recommend {
+        user_id: String,
+        /// Number of items.
+        #[arg(short, default_value_t = 10)]
+        n: usize,
+    },
+    /// Similar items of an item, or similar users of a user.
+    Neighbors {
+        #[arg(value_enum)]
+        kind: NeighborKind,
+        id: String,
+        /// Name of the recommender, ignored by servers before 0.5.
+        #[arg(long, default_value = "neighbors")]
+        recommender: String,
+        /// Number of neighbors.
+        #[arg(short, default_value_t = 10)]
+        n: usize,
+    },
+    /// Import users, items or feedback from a file.
+    Import {
+        #[arg(value_enum)]
+        kind: RecordKind,
+        file: PathBuf,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Export all users, items or feedback to a file, or `-` for the
+    /// standard output.
+    Export {
+        #[arg(value_enum)]
+        kind: RecordKind,
+        file: PathBuf,
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Check that the server is live, or ready with `--ready`. Exits with a
+    /// failure if it is not.
+    Health {
+        #[arg(long)]
+        ready: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum UserCommand {
+    Insert {
+        user_id: String,
+        /// Labels as JSON.
+        #[arg(long, value_parser = parse_json)]
+        labels: Option<Value>,
+        #[arg(long, default_value = "")]
+        comment: String,
+    },
+    Get {
+        user_id: String,
+    },
+    Delete {
+        user_id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ItemCommand {
+    Insert {
+        item_id: String,
+        /// Labels as JSON.
+        #[arg(long, value_parser = parse_json)]
+        labels: Option<Value>,
+        /// Comma separated categories.
+        #[arg(long, value_delimiter = ',')]
+        categories: Vec<String>,
+        #[arg(long)]
+        hidden: bool,
+        /// Timestamp in RFC 3339.
+        #[arg(long, default_value = "")]
+        timestamp: String,
+        #[arg(long, default_value = "")]
+        comment: String,
+    },
+    Get {
+        item_id: String,
+    },
+    Delete {
+        item_id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum FeedbackCommand {
+    Insert {
+        #[arg(long = "type")]
+        feedback_type: String,
+        #[arg(long = "user")]
+        user_id: String,
+        #[arg(long = "item")]
+        item_id: String,
+        #[arg(long, default_value_t = 0.0)]
+        value: f64,
+        /// Timestamp in RFC 3339.
+        #[arg(long, default_value = "")]
+        timestamp: String,
+    },
+    /// List the feedback of a type of a user.
+    List {
+        user_id: String,
+        feedback_type: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum NeighborKind {
+    Item,
+    User,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum RecordKind {
+    Users,
+    Items,
+    Feedback,
+}
+
+#[derive(Debug, Args)]
+struct FormatArgs {
+    /// Format of the file, from its extension by default: JSON Lines for
+    /// `.jsonl` and `.ndjson`, CSV otherwise.
+    #[arg(long, value_enum)]
+    format: Option<FileFormat>,
+    /// CSV field delimiter.
+    #[arg(long, default_value_t = ',')]
+    delimiter: char,
+    /// CSV files without a header row.
+    #[arg(long)]
+    no_header: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum FileFormat {
+    Csv,
+    Jsonl,
+}
+
+impl FormatArgs {
+    fn format(&self, path: &Path) -> Result<Format, Error> {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let format = self.format.unwrap_or(match extension {
+            Some("jsonl" | "ndjson") => FileFormat::Jsonl,
+            _ => FileFormat::Csv,
+        });
+        if format == FileFormat::Jsonl {
+            return Ok(Format::JsonLines);
+        }
+        let delimiter = u8::try_from(self.delimiter)
+            .ok()
+            .filter(u8::is_ascii)
+            .ok_or_else(|| Error::Config("the CSV delimiter must be ASCII".into()))?;
+        Ok(Format::Csv(CsvOptions {
+            delimiter,
+            header: !self.no_header,
+            ..CsvOptions::default()
+        }))
+    }
+}
+
+fn parse_json(value: &str) -> Result<Value, serde_json::Error> {
+    serde_json::from_str(value)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<ExitCode, Error> {
+    let client = client(cli)?;
+    let output = cli.output;
+    match &cli.command {
+        Command::User(command) => match command {
+            UserCommand::Insert {
+                user_id,
+                labels,
+                comment,
+            } => {
+                let user = User {
+                    user_id: user_id.clone(),
+                    labels: labels.clone().unwrap_or(Value::Null),
+                    comment: comment.clone(),
+                };
+                print(output, &[client.insert_user(&user)?])
+            }
+            UserCommand::Get { user_id } => print(output, &[client.get_user(user_id)?]),
+            UserCommand::Delete { user_id } => print(output, &[client.delete_user(user_id)?]),
+        },
+        Command::Item(command) => match command {
+            ItemCommand::Insert {
+                item_id,
+                labels,
+                categories,
+                hidden,
+                timestamp,
+                comment,
+            } => {
+                let item = Item {
+                    item_id: item_id.clone(),
+                    is_hidden: *hidden,
+                    labels: labels.clone().unwrap_or(Value::Null),
+                    categories: categories.clone(),
+                    timestamp: timestamp.clone(),
+                    comment: comment.clone(),
+                };
+                print(output, &[client.insert_item(&item)?])
+            }
+            ItemCommand::Get { item_id } => print(output, &[client.get_item(item_id)?]),
+            ItemCommand::Delete { item_id } => print(output, &[client.delete_item(item_id)?]),
+        },
+        Command::Feedback(command) => match command {
+            FeedbackCommand::Insert {
+                feedback_type,
+                user_id,
+                item_id,
+                value,
+                timestamp,
+            } => {
+                let feedback = Feedback {
+                    feedback_type: feedback_type.clone(),
+                    user_id: user_id.clone(),
+                    item_id: item_id.clone(),
+                    value: *value,
+                    timestamp: timestamp.clone(),
+                };
+                print(output, &[client.insert_feedback(&[feedback])?])
+            }
+            FeedbackCommand::List {
+                user_id,
+                feedback_type,
+            } => print(output, &client.list_feedback(user_id, feedback_type)?),
+        },
+        Command::Recommend { user_id, n } => {
+            match client.get_recommend(user_id, RecommendOptions { n: *n }) {
+                Err(Error::Unsupported(_)) => {
+                    // Servers before 0.5 recommend item ids without scores.
+                    print(
+                        output,
+                        &client.get_recommend_ids(user_id, RecommendOptions { n: *n })?,
+                    )
+                }
+                scores => print(output, &scores?),
+            }
+        }
+        Command::Neighbors {
+            kind,
+            id,
+            recommender,
+            n,
+        } => {
+            let options = RecommendOptions { n: *n };
+            let scores = match kind {
+                NeighborKind::Item => client.get_item_to_item(recommender, id, options)?,
+                NeighborKind::User => client.get_user_to_user(recommender, id, options)?,
+            };
+            print(output, &scores)
+        }
+        Command::Import { kind, file, format } => {
+            let format = format.format(file)?;
+            match kind {
+                RecordKind::Users => import::<User>(&client, file, &format),
+                RecordKind::Items => import::<Item>(&client, file, &format),
+                RecordKind::Feedback => import::<Feedback>(&client, file, &format),
+            }
+        }
+        Command::Export { kind, file, format } => {
+            let format = format.format(file)?;
+            match kind {
+                RecordKind::Users => export::<User>(&client, file, &format),
+                RecordKind::Items => export::<Item>(&client, file, &format),
+                RecordKind::Feedback => export::<Feedback>(&client, file, &format),
+            }
+        }
+        Command::Health { ready } => return health(&client, *ready, output),
+    }?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the health of the server. A server that is not ready, or that has
+/// a disconnected store when checking readiness, fails the command, so that
+/// scripts and probes see it in the exit code.
+fn health(client: &Gorse, ready: bool, output: Output) -> Result<ExitCode, Error> {
+    let status = match ready {
+        true => client.health_ready()?,
+        false => client.health_live()?,
+    };
+    print(output, std::slice::from_ref(&status))?;
+    let healthy =
+        status.ready && (!ready || (status.data_store_connected && status.cache_store_connected));
+    Ok(match healthy {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    })
+}
+
+fn client(cli: &Cli) -> Result<Gorse, Error> {
+    let mut config = match &cli.config {
+        Some(path) => GorseConfig::from_file(path)?,
+        None => GorseConfig::from_env()?,
+    };
+    if let Some(entry_point) = &cli.entry_point {
+        config.entry_point = Some(entry_point.clone());
+        config.endpoints.clear();
+    }
+    if let Some(api_key) = &cli.api_key {
+        config.api_key = Some(ApiKey::from(api_key.clone()));
+        config.api_key_file = None;
+    }
+    config.build_blocking()
+}
+
+fn import<T: Record>(client: &Gorse, path: &Path, format: &Format) -> Result<(), Error> {
+    let report = client.import::<T>(File::open(path)?, format, |report| {
+        eprint!("\r{} rows, {} inserted", report.rows, report.inserted);
+    });
+    eprintln!();
+    let report = report.map_err(|err| {
+        eprintln!("{} rows inserted before the failure", err.report.inserted);
+        err.source
+    })?;
+    for error in &report.errors {
+        eprintln!("row {}: {}", error.row, error.message);
+    }
+    println!(
+        "{} rows, {} inserted, {} invalid",
+        report.rows,
+        report.inserted,
+        report.errors.len()
+    );
+    Ok(())
+}
+
+fn export<T: Record>(client: &Gorse, path: &Path, format: &Format) -> Result<(), Error> {
+    let writer: Box<dyn Write> = if path == Path::new("-") {
+        Box::new(std::io::stdout().lock())
+    } else {
+        Box::new(BufWriter::new(File::create(path)?))
+    };
+    let rows = client.export::<T>(writer, format, |rows| eprint!("\r{} rows", rows))?;
+    eprintln!("\r{} rows exported", rows);
+    Ok(())
+}
+
+/// Value printed as a table row.
+trait Row: Serialize {
+    const HEADER: &'static [&'static str];
+
+    fn cells(&self) -> Vec<String>;
+}
+
+impl Row for User {
+    const HEADER: &'static [&'static str] = &["USER", "LABELS", "COMMENT"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.user_id.clone(),
+            self.labels.to_string(),
+            self.comment.clone(),
+        ]
+    }
+}
+
+impl Row for Item {
+    const HEADER: &'static [&'static str] = &[
+        "ITEM",
+        "HIDDEN",
+        "CATEGORIES",
+        "TIMESTAMP",
+        "LABELS",
+        "COMMENT",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.item_id.clone(),
+            self.is_hidden.to_string(),
+            self.categories.join(","),
+            self.timestamp.clone(),
+            self.labels.to_string(),
+            self.comment.clone(),
+        ]
+    }
+}
+
+impl Row for Feedback {
+    const HEADER: &'static [&'static str] = &["TYPE", "USER", "ITEM", "VALUE", "TIMESTAMP"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.feedback_type.clone(),
+            self.user_id.clone(),
+            self.item_id.clone(),
+            self.value.to_string(),
+            self.timestamp.clone(),
+        ]
+    }
+}
+
+impl Row for Score {
+    const HEADER: &'static [&'static str] = &["ID", "SCORE"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.id.clone(), self.score.to_string()]
+    }
+}
+
+impl Row for String {
+    const HEADER: &'static [&'static str] = &["ID"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.clone()]
+    }
+}
+
+impl Row for RowAffected {
+    const HEADER: &'static [&'static str] = &["ROWS AFFECTED"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.row_affected.to_string()]
+    }
+}
+
+impl Row for HealthStatus {
+    const HEADER: &'static [&'static str] = &["READY", "DATA STORE", "CACHE STORE"];
+
+    fn cells(&self) -> Vec<String> {
+        let store = |connected: bool, error: &Option<Value>| match (connected, error) {
+            (true, _) => "connected".to_string(),
+            (false, Some(error)) => error.to_string(),
+            (false, None) => "disconnected".to_string(),
+        };
+        vec![
+            self.ready.to_string(),
+            store(self.data_store_connected, &self.data_store_error),
+            store(self.cache_store_connected, &self.cache_store_error),
+        ]
+    }
+}
+
+fn print<T: Row>(output: Output, rows: &[T]) -> Result<(), Error> {
+    let mut stdout = std::io::stdout().lock();
+    match output {
+        Output::Json => {
+            serde_json::to_writer_pretty(&mut stdout, rows)?;
+            writeln!(stdout)?;
+        }
+        Output::Table => stdout.write_all(table(rows).as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Formats rows as a table with a header, in columns padded to the widest
+/// cell.
+fn table<T: Row>(rows: &[T]) -> String {
+    let header = T::HEADER.iter().map(|name| name.to_string()).collect();
+    let lines: Vec<Vec<String>> = std::iter::once(header)
+        .chain(rows.iter().map(Row::cells))
+        .collect();
+    let mut widths = vec![0; T::HEADER.len()];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for line in &lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+        let cli = Cli::parse_from(["gorse", "import", "feedback", "a.jsonl", "--output", "json"]);
+        assert_eq!(cli.output, Output::Json);
+        let Command::Import { format, file, .. } = cli.command else {
+            panic!("unexpected command: {:?}", cli.command);
+        };
+        assert!(matches!(format.format(&file), Ok(Format::JsonLines)));
+    }
+
+    #[test]
+    fn test_health() -> Result<(), Error> {
+        let (entry_point, server) = test_server::serve(2, |head, _| {
+            if head.starts_with("get /api/health/ready ") {
+                test_server::response(
+                    "503 Service Unavailable",
+                    "application/json",
+                    r#"{"Ready":false,"DataStoreConnected":false,"CacheStoreConnected":true}"#,
+                )
+            } else {
+                test_server::json(
+                    r#"{"Ready":true,"DataStoreConnected":true,"CacheStoreConnected":true}"#,
+                )
+            }
+        });
+        let client = Gorse::new(entry_point, "");
+        assert_eq!(health(&client, false, Output::Json)?, ExitCode::SUCCESS);
+        assert_eq!(health(&client, true, Output::Json)?, ExitCode::FAILURE);
+        server.join().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_table() {
+        let scores = vec![
+            Score {
+                id: "vuejs:vue".into(),
+                score: 0.5,
+            },
+            Score {
+                id: "a".into(),
+                score: 1.0,
+            },
+        ];
+        assert_eq!(
+            table(&scores),
+            "ID         SCORE\nvuejs:vue  0.5\na          1\n"
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use crate::compression::{Compression, RequestCompression};
+use crate::credentials::Credentials;
+use crate::endpoint::{EndpointPool, LoadBalanceStrategy, RetryPolicy};
+use crate::middleware::Middlewares;
+use crate::observer::Observers;
+#[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+use crate::tls::TlsConfig;
+use crate::{
+    ApiKey, CircuitBreaker, CredentialsProvider, Error, Gorse, Middleware, Observer, Result,
+    ServerInfo, ServerVersion, StaticCredentials,
+};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Builder for [`Gorse`] and [`blocking::Gorse`](crate::blocking::Gorse) clients.
+///
+/// ```no_run
+/// use gorse_rs::{Gorse, LoadBalanceStrategy};
+///
+/// let client = Gorse::builder()
+///     .endpoints(["http://10.0.0.1:8087", "http://10.0.0.2:8087"])
+///     .api_key("api_key")
+///     .strategy(LoadBalanceStrategy::LeastOutstanding)
+///     .build()?;
+/// # Ok::<(), gorse_rs::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct GorseBuilder {
+    endpoints: Vec<String>,
+    credentials: Credentials,
+    strategy: LoadBalanceStrategy,
+    failure_threshold: u32,
+    probe_interval: Duration,
+    max_retries: u32,
+    retry_backoff: Duration,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    observers: Observers,
+    middlewares: Middlewares,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    batch_size: Option<usize>,
+    server_version: Option<ServerVersion>,
+    probe_server_version: bool,
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    compression: Option<Compression>,
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    compression_threshold: Option<usize>,
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    tls: TlsConfig,
+    #[cfg(feature = "tower")]
+    service: Option<crate::service::HttpService>,
+}
+
+impl Default for GorseBuilder {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            credentials: Credentials::default(),
+            strategy: LoadBalanceStrategy::default(),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            probe_interval: DEFAULT_PROBE_INTERVAL,
+            max_retries: 0,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            circuit_breaker: None,
+            observers: Observers::default(),
+            middlewares: Middlewares::default(),
+            timeout: None,
+            connect_timeout: None,
+            batch_size: None,
+            server_version: None,
+            probe_server_version: false,
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compression: None,
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compression_threshold: None,
+            #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+            tls: TlsConfig::default(),
+            #[cfg(feature = "tower")]
+            service: None,
+        }
+    }
+}
+
+impl GorseBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a Gorse server endpoint.
+    pub fn entry_point(mut self, entry_point: impl Into<String>) -> Self {
+        self.endpoints.push(entry_point.into());
+        self
+    }
+
+    /// Adds several Gorse server endpoints.
+    pub fn endpoints<I, S>(mut self, endpoints: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.endpoints.extend(endpoints.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.credentials = Credentials(Arc::new(StaticCredentials(ApiKey::new(api_key))));
+        self
+    }
+
+    /// Sets the source of the API key, asked before each request so that the
+    /// key can be rotated. Replaces the key set by [`api_key`](Self::api_key).
+    pub fn credentials(mut self, credentials: impl CredentialsProvider + 'static) -> Self {
+        self.credentials = Credentials(Arc::new(credentials));
+        self
+    }
+
+    /// Sets how requests are spread across endpoints.
+    pub fn strategy(mut self, strategy: LoadBalanceStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets the number of consecutive failures after which an endpoint is
+    /// considered unhealthy.
+    pub fn failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold;
+        self
+    }
+
+    /// Sets how often unhealthy endpoints are probed through the health endpoint.
+    pub fn probe_interval(mut self, probe_interval: Duration) -> Self {
+        self.probe_interval = probe_interval;
+        self
+    }
+
+    /// Sets how many times a read that failed on every endpoint is retried,
+    /// by default never. Writes are never retried.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before the first retry, doubled before each next one.
+    pub fn retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// Guards requests with a circuit breaker, so that requests fail fast with
+    /// [`Error::CircuitOpen`] while the server is degraded.
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(Arc::new(circuit_breaker));
+        self
+    }
+
+    /// Registers an observer notified around each request, e.g.
+    /// [`MetricsObserver`](crate::MetricsObserver) with the `metrics` feature.
+    pub fn observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observers.0.push(Arc::new(observer));
+        self
+    }
+
+    /// Appends a middleware to the chain applied to each request.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middlewares.0.push(Arc::new(middleware));
+        self
+    }
+
+    /// Sets the timeout of each request, from connecting until the response
+    /// body has been read, or with a [`service`](Self::service) until the
+    /// service returns the response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for connecting to an endpoint.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Splits feedback, users and items inserted with a single call into
+    /// requests of at most `batch_size` records.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Sets the version of the Gorse server, so that recommenders are
+    /// fetched from the routes it serves. Without it, the routes of the
+    /// latest server are used unless
+    /// [`probe_server_version`](Self::probe_server_version) is set.
+    pub fn server_version(mut self, server_version: ServerVersion) -> Self {
+        self.server_version = Some(server_version);
+        self
+    }
+
+    /// Asks the server for its version before the first version dependent
+    /// call, see [`Gorse::server_info`]. Ignored if the version is set with
+    /// [`server_version`](Self::server_version).
+    pub fn probe_server_version(mut self, probe_server_version: bool) -> Self {
+        self.probe_server_version = probe_server_version;
+        self
+    }
+
+    /// Compresses request bodies of at least 1 KiB, or
+    /// [`compression_threshold`](Self::compression_threshold), e.g. feedback
+    /// batches. Compressed responses are accepted whenever the `gzip` or
+    /// `zstd` feature is enabled.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Sets the minimum size in bytes of the request bodies compressed with
+    /// [`compression`](Self::compression).
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub fn compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression_threshold = Some(threshold);
+        self
+    }
+
+    /// Trusts the certificates of a PEM encoded bundle in addition to the
+    /// system roots, e.g. the CA of a private Gorse cluster.
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    pub fn ca_bundle_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.tls.ca_bundles.push(pem.into());
+        self
+    }
+
+    /// Authenticates with a client certificate for mutual TLS, given a PEM
+    /// encoded certificate chain and PKCS#8 private key.
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    pub fn client_identity_pem(
+        mut self,
+        certificate: impl Into<Vec<u8>>,
+        key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.tls.identity = Some((certificate.into(), key.into()));
+        self
+    }
+
+    /// Sends the requests of the async client through a `tower` service, e.g.
+    /// a `hyper` client wrapped in layers, instead of the built-in reqwest
+    /// client. The service replaces the connection settings of the built-in
+    /// client, so that [`build`](Self::build) fails if the connect timeout
+    /// or TLS settings are set too: configure them on the service instead.
+    /// The [`timeout`](Self::timeout) of requests, and the shorter one of
+    /// health probes, still apply to the calls of the service.
+    #[cfg(feature = "tower")]
+    pub fn service<S, B>(mut self, service: S) -> Self
+    where
+        S: tower::Service<http::Request<reqwest::Body>, Response = http::Response<B>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        S::Future: Send + 'static,
+        B: http_body::Body + Send + Sync + 'static,
+        B::Data: Into<bytes::Bytes>,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.service = Some(crate::service::HttpService::new(service));
+        self
+    }
+
+    fn check_schemes(&self) -> Result<()> {
+        #[cfg(not(any(feature = "rustls-tls", feature = "native-tls")))]
+        if self.endpoints.iter().any(|url| url.starts_with("https:")) {
+            return Err(Error::Config(
+                "HTTPS endpoints require the rustls-tls or native-tls feature".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that no settings of the built-in client are set along with a
+    /// `tower` service, which would silently ignore them.
+    fn check_service(&self) -> Result<()> {
+        #[cfg(feature = "tower")]
+        if self.service.is_some() {
+            let mut ignored = Vec::new();
+            if self.connect_timeout.is_some() {
+                ignored.push("connect_timeout");
+            }
+            #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+            if !self.tls.ca_bundles.is_empty() || self.tls.identity.is_some() {
+                ignored.push("TLS settings");
+            }
+            if !ignored.is_empty() {
+                return Err(Error::Config(format!(
+                    "{} cannot be combined with a tower service",
+                    ignored.join(" and ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn endpoint_pool(&self) -> Result<Arc<EndpointPool>> {
+        if self.endpoints.is_empty() {
+            return Err(Error::Config("at least one endpoint is required".into()));
+        }
+        Ok(Arc::new(EndpointPool::new(
+            self.endpoints.clone(),
+            self.strategy,
+            self.failure_threshold,
+            self.probe_interval,
+            RetryPolicy {
+                max_retries: self.max_retries,
+                backoff: self.retry_backoff,
+            },
+        )))
+    }
+
+    fn server_info(&self) -> Arc<OnceLock<ServerInfo>> {
+        let server_info = OnceLock::new();
+        if let Some(version) = self.server_version {
+            let _ = server_info.set(ServerInfo::new(version));
+        }
+        Arc::new(server_info)
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    fn request_compression(&self) -> Option<RequestCompression> {
+        self.compression.map(|compression| RequestCompression {
+            compression,
+            threshold: self
+                .compression_threshold
+                .unwrap_or(DEFAULT_COMPRESSION_THRESHOLD),
+        })
+    }
+
+    fn client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+        {
+            builder = builder.tls_certs_merge(self.tls.certificates()?);
+            if let Some(identity) = self.tls.identity()? {
+                builder = builder.identity(identity);
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        Ok(builder.build()?)
+    }
+
+    #[cfg(feature = "blocking")]
+    fn blocking_client(&self) -> Result<reqwest::blocking::Client> {
+        let mut builder = reqwest::blocking::Client::builder();
+        #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+        {
+            builder = builder.tls_certs_merge(self.tls.certificates()?);
+            if let Some(identity) = self.tls.identity()? {
+                builder = builder.identity(identity);
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        Ok(builder.build()?)
+    }
+
+    pub fn build(self) -> Result<Gorse> {
+        self.check_schemes()?;
+        self.build_unchecked()
+    }
+
+    /// Builds a client without checking that the schemes of the endpoints
+    /// are supported, so that requests to them fail instead.
+    pub(crate) fn build_unchecked(self) -> Result<Gorse> {
+        self.check_service()?;
+        Ok(Gorse {
+            endpoints: self.endpoint_pool()?,
+            client: self.client()?,
+            server_info: self.server_info(),
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compression: self.request_compression(),
+            credentials: self.credentials,
+            circuit_breaker: self.circuit_breaker,
+            observers: self.observers,
+            middlewares: self.middlewares,
+            batch_size: self.batch_size,
+            probe_server_version: self.probe_server_version,
+            #[cfg(feature = "tower")]
+            service: self
+                .service
+                .map(|service| service.with_timeout(self.timeout)),
+        })
+    }
+
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(self) -> Result<crate::blocking::Gorse> {
+        self.check_schemes()?;
+        self.build_blocking_unchecked()
+    }
+
+    /// Builds a blocking client without checking that the schemes of the
+    /// endpoints are supported, so that requests to them fail instead.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn build_blocking_unchecked(self) -> Result<crate::blocking::Gorse> {
+        #[cfg(feature = "tower")]
+        if self.service.is_some() {
+            return Err(Error::Config(
+                "tower services are only supported by the async client".into(),
+            ));
+        }
+        Ok(crate::blocking::Gorse {
+            endpoints: self.endpoint_pool()?,
+            client: self.blocking_client()?,
+            server_info: self.server_info(),
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compression: self.request_compression(),
+            credentials: self.credentials,
+            circuit_breaker: self.circuit_breaker,
+            observers: self.observers,
+            middlewares: self.middlewares,
+            batch_size: self.batch_size,
+            probe_server_version: self.probe_server_version,
+        })
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+type StateListener = Arc<dyn Fn(CircuitState, CircuitState) + Send + Sync>;
+
+/// State of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally and outcomes are recorded.
+    Closed,
+    /// Requests fail immediately with [`Error::CircuitOpen`] until the cool-down elapses.
+    Open,
+    /// A limited number of trial requests decide whether to close or reopen the circuit.
+    HalfOpen,
+}
+
+/// Circuit breaker guarding the requests of a client.
+///
+/// The breaker opens once the failure rate over the last `window_size`
+/// requests reaches `failure_rate_threshold`, provided at least
+/// `minimum_calls` requests were recorded. Connection errors and server errors
+/// count as failures, while errors raised before sending a request, e.g. by
+/// a credentials provider, are not recorded. After `cool_down`, up to
+/// `half_open_calls` trial requests are let through: the circuit closes if
+/// all of them succeed and reopens as soon as one fails.
+///
+/// ```
+/// use std::time::Duration;
+/// use gorse_rs::{CircuitBreaker, Gorse};
+///
+/// let breaker = CircuitBreaker::new()
+///     .failure_rate_threshold(0.5)
+///     .cool_down(Duration::from_secs(30))
+///     .on_state_change(|from, to| eprintln!("gorse circuit: {:?} -> {:?}", from, to));
+/// let client = Gorse::builder()
+///     .entry_point("http://127.0.0.1:8087")
+///     .circuit_breaker(breaker)
+///     .build()?;
+/// # Ok::<(), gorse_rs::Error>(())
+/// ```
+pub struct CircuitBreaker {
+    failure_rate_threshold: f64,
+    minimum_calls: usize,
+    window_size: usize,
+    cool_down: Duration,
+    half_open_calls: usize,
+    listeners: Vec<StateListener>,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    state: CircuitState,
+    /// Outcomes of the latest requests while closed, `true` for failures.
+    window: VecDeque<bool>,
+    opened_at: Instant,
+    trials: usize,
+    successes: usize,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_rate_threshold: 0.5,
+            minimum_calls: 10,
+            window_size: 20,
+            cool_down: Duration::from_secs(30),
+            half_open_calls: 1,
+            listeners: Vec::new(),
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                window: VecDeque::new(),
+                opened_at: Instant::now(),
+                trials: 0,
+                successes: 0,
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("state", &self.state())
+            .field("failure_rate_threshold", &self.failure_rate_threshold)
+            .field("minimum_calls", &self.minimum_calls)
+            .field("window_size", &self.window_size)
+            .field("cool_down", &self.cool_down)
+            .field("half_open_calls", &self.half_open_calls)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the failure rate, between 0 and 1, at which the circuit opens.
+    pub fn failure_rate_threshold(mut self, threshold: f64) -> Self {
+        self.failure_rate_threshold = threshold;
+        self
+    }
+
+    /// Sets the number of recorded requests required before the failure rate is evaluated.
+    pub fn minimum_calls(mut self, minimum_calls: usize) -> Self {
+        self.minimum_calls = minimum_calls.max(1);
+        self
+    }
+
+    /// Sets the number of latest requests the failure rate is computed over.
+    pub fn window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size.max(1);
+        self
+    }
+
+    /// Sets how long the circuit stays open before trial requests are allowed.
+    pub fn cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Sets the number of trial requests let through while half-open.
+    pub fn half_open_calls(mut self, half_open_calls: usize) -> Self {
+        self.half_open_calls = half_open_calls.max(1);
+        self
+    }
+
+    /// Registers a callback invoked with the previous and the new state on each transition.
+    pub fn on_state_change<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(CircuitState, CircuitState) + Send + Sync + 'static,
+    {
+        self.listeners.push(Arc::new(listener));
+        self
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> CircuitState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Asks permission to send a request, failing with [`Error::CircuitOpen`]
+    /// while the circuit is open.
+    pub(crate) fn acquire(&self) -> Result<Permit<'_>> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut transition = None;
+        if inner.state == CircuitState::Open && inner.opened_at.elapsed() >= self.cool_down {
+            transition = self.transition(&mut inner, CircuitState::HalfOpen);
+        }
+        let permitted = match inner.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen if inner.trials < self.half_open_calls => {
+                inner.trials += 1;
+                true
+            }
+            CircuitState::HalfOpen => false,
+        };
+        let state = inner.state;
+        drop(inner);
+        self.notify(transition);
+        if permitted {
+            Ok(Permit {
+                breaker: self,
+                state,
+                failure: None,
+            })
+        } else {
+            Err(Error::CircuitOpen)
+        }
+    }
+
+    fn complete(&self, state: CircuitState, failure: Option<bool>) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state != state {
+            // The outcome belongs to a previous state and no longer matters.
+            return;
+        }
+        let transition = match (inner.state, failure) {
+            (CircuitState::Closed, Some(failure)) => {
+                inner.window.push_back(failure);
+                if inner.window.len() > self.window_size {
+                    inner.window.pop_front();
+                }
+                let failures = inner.window.iter().filter(|&&failure| failure).count();
+                let calls = inner.window.len();
+                if calls >= self.minimum_calls
+                    && failures as f64 >= self.failure_rate_threshold * calls as f64
+                {
+                    self.transition(&mut inner, CircuitState::Open)
+                } else {
+                    None
+                }
+            }
+            (CircuitState::HalfOpen, Some(true)) => self.transition(&mut inner, CircuitState::Open),
+            (CircuitState::HalfOpen, Some(false)) => {
+                inner.successes += 1;
+                if inner.successes >= self.half_open_calls {
+                    self.transition(&mut inner, CircuitState::Closed)
+                } else {
+                    None
+                }
+            }
+            (CircuitState::HalfOpen, None) => {
+                // The request was abandoned, so its trial slot is released.
+                inner.trials -= 1;
+                None
+            }
+            _ => None,
+        };
+        drop(inner);
+        self.notify(transition);
+    }
+
+    fn transition(
+        &self,
+        inner: &mut Inner,
+        state: CircuitState,
+    ) -> Option<(CircuitState, CircuitState)> {
+        let from = inner.state;
+        inner.state = state;
+        inner.window.clear();
+        inner.trials = 0;
+        inner.successes = 0;
+        if state == CircuitState::Open {
+            inner.opened_at = Instant::now();
+        }
+        Some((from, state))
+    }
+
+    fn notify(&self, transition: Option<(CircuitState, CircuitState)>) {
+        if let Some((from, to)) = transition {
+            for listener in &self.listeners {
+                listener(from, to);
+            }
+        }
+    }
+}
+
+/// Permission to send one request, recording its outcome when dropped.
+pub(crate) struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    state: CircuitState,
+    failure: Option<bool>,
+}
+
+impl Permit<'_> {
+    pub(crate) fn record(&mut self, success: bool) {
+        self.failure = Some(!success);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.breaker.complete(self.state, self.failure);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn call(breaker: &CircuitBreaker, success: bool) -> bool {
+        match breaker.acquire() {
+            Ok(mut permit) => {
+                permit.record(success);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let transitions = Arc::new(AtomicUsize::new(0));
+        let counter = transitions.clone();
+        let breaker = CircuitBreaker::new()
+            .minimum_calls(4)
+            .window_size(4)
+            .failure_rate_threshold(0.5)
+            .cool_down(Duration::ZERO)
+            .on_state_change(move |_, _| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        assert!(call(&breaker, true));
+        assert!(call(&breaker, false));
+        assert!(call(&breaker, true));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(call(&breaker, false));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // The cool-down is over, so a single trial request is let through.
+        let mut permit = breaker.acquire().unwrap();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(matches!(breaker.acquire(), Err(Error::CircuitOpen)));
+        permit.record(true);
+        drop(permit);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(transitions.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_half_open_failure() {
+        let breaker = CircuitBreaker::new()
+            .minimum_calls(1)
+            .cool_down(Duration::from_millis(50));
+        assert!(call(&breaker, false));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!call(&breaker, true));
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(call(&breaker, false));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!call(&breaker, true));
+    }
+}
@@ -0,0 +1,141 @@
+use std::io::Write;
+
+use serde::Deserialize;
+
+use crate::Result;
+
+/// Content encoding used to compress request bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Value of the `Content-Encoding` header.
+    pub fn content_encoding(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                let mut encoder = zstd::Encoder::new(Vec::new(), 0)?;
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+}
+
+/// Compression of request bodies of at least `threshold` bytes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RequestCompression {
+    pub(crate) compression: Compression,
+    pub(crate) threshold: usize,
+}
+
+/// Compresses a request body if it is large enough, returning the body to
+/// send and its content encoding.
+pub(crate) fn compress(
+    compression: Option<RequestCompression>,
+    bytes: Vec<u8>,
+) -> Result<(Vec<u8>, Option<&'static str>)> {
+    match compression {
+        Some(RequestCompression {
+            compression,
+            threshold,
+        }) if bytes.len() >= threshold => Ok((
+            compression.compress(&bytes)?,
+            Some(compression.content_encoding()),
+        )),
+        _ => Ok((bytes, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Gorse;
+
+    #[test]
+    fn test_compress() -> Result<()> {
+        let body = br#"{"FeedbackType":"star","UserId":"bob","ItemId":"1"}"#.repeat(100);
+        let small = body[..10].to_vec();
+        #[cfg(feature = "gzip")]
+        {
+            let compression = Some(RequestCompression {
+                compression: Compression::Gzip,
+                threshold: 100,
+            });
+            assert_eq!(compress(compression, small.clone())?, (small.clone(), None));
+            let (compressed, encoding) = compress(compression, body.clone())?;
+            assert_eq!(encoding, Some("gzip"));
+            assert!(compressed.len() < body.len() / 10);
+            let mut decoded = Vec::new();
+            std::io::Read::read_to_end(
+                &mut flate2::read::GzDecoder::new(compressed.as_slice()),
+                &mut decoded,
+            )?;
+            assert_eq!(decoded, body);
+        }
+        #[cfg(feature = "zstd")]
+        {
+            let compression = Some(RequestCompression {
+                compression: Compression::Zstd,
+                threshold: 100,
+            });
+            assert_eq!(compress(compression, small.clone())?, (small, None));
+            let (compressed, encoding) = compress(compression, body.clone())?;
+            assert_eq!(encoding, Some("zstd"));
+            assert_eq!(zstd::decode_all(compressed.as_slice())?, body);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_threshold() -> Result<()> {
+        #[cfg(feature = "gzip")]
+        let compression = Compression::Gzip;
+        #[cfg(not(feature = "gzip"))]
+        let compression = Compression::Zstd;
+        for builder in [
+            Gorse::builder()
+                .compression(compression)
+                .compression_threshold(100),
+            Gorse::builder()
+                .compression_threshold(100)
+                .compression(compression),
+        ] {
+            let client = builder.entry_point("http://127.0.0.1:8087").build()?;
+            assert_eq!(client.compression.unwrap().threshold, 100);
+        }
+        let client = Gorse::builder()
+            .entry_point("http://127.0.0.1:8087")
+            .compression(compression)
+            .build()?;
+        assert_eq!(client.compression.unwrap().threshold, 1024);
+        let client = Gorse::builder()
+            .entry_point("http://127.0.0.1:8087")
+            .compression_threshold(100)
+            .build()?;
+        assert!(client.compression.is_none());
+        Ok(())
+    }
+}
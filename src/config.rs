@@ -0,0 +1,373 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{
+    ApiKey, Error, FileCredentials, Gorse, GorseBuilder, LoadBalanceStrategy, Result, ServerVersion,
+};
+
+/// Client configuration, read from environment variables with
+/// [`GorseConfig::from_env`] or, with the `config` feature, from a TOML, JSON
+/// or YAML file with [`GorseConfig::from_file`]:
+///
+/// ```toml
+/// endpoints = ["http://10.0.0.1:8087", "http://10.0.0.2:8087"]
+/// api_key_file = "/run/secrets/gorse_api_key"
+/// timeout_ms = 5000
+/// strategy = "least_outstanding"
+/// max_retries = 2
+/// batch_size = 1000
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GorseConfig {
+    /// A Gorse server endpoint, added before `endpoints`.
+    pub entry_point: Option<String>,
+    pub endpoints: Vec<String>,
+    pub api_key: Option<ApiKey>,
+    /// File the API key is read from, read again whenever it changes.
+    /// Exclusive with `api_key`.
+    pub api_key_file: Option<PathBuf>,
+    pub timeout_ms: Option<u64>,
+    pub connect_timeout_ms: Option<u64>,
+    pub strategy: Option<LoadBalanceStrategy>,
+    /// Consecutive failures after which an endpoint is considered unhealthy.
+    pub failure_threshold: Option<u32>,
+    /// Interval between health probes of unhealthy endpoints.
+    pub probe_interval_ms: Option<u64>,
+    /// Number of retries of reads that failed on every endpoint.
+    pub max_retries: Option<u32>,
+    /// Delay before the first retry, doubled before each next one.
+    pub retry_backoff_ms: Option<u64>,
+    /// Maximum number of records sent in a single batch request.
+    pub batch_size: Option<usize>,
+    /// Version of the Gorse server, e.g. `"0.4.15"`, selecting the routes of
+    /// recommenders.
+    pub server_version: Option<ServerVersion>,
+    /// Whether to ask the server for its version, if not set.
+    pub probe_server_version: Option<bool>,
+    /// Compression of large request bodies.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub compression: Option<crate::Compression>,
+    /// Minimum size in bytes of compressed request bodies.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub compression_threshold: Option<usize>,
+    /// PEM bundle of CAs trusted in addition to the system roots.
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    pub ca_bundle_file: Option<PathBuf>,
+    /// PEM encoded client certificate chain for mutual TLS.
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    pub client_certificate_file: Option<PathBuf>,
+    /// PEM encoded PKCS#8 private key of the client certificate.
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    pub client_key_file: Option<PathBuf>,
+}
+
+impl GorseConfig {
+    /// Reads the configuration from environment variables:
+    ///
+    /// - `GORSE_ENTRY_POINT`: comma separated list of endpoints,
+    /// - `GORSE_API_KEY` or `GORSE_API_KEY_FILE`,
+    /// - `GORSE_TIMEOUT_MS` and `GORSE_CONNECT_TIMEOUT_MS`,
+    /// - `GORSE_MAX_RETRIES` and `GORSE_RETRY_BACKOFF_MS`,
+    /// - `GORSE_BATCH_SIZE`,
+    /// - `GORSE_SERVER_VERSION` or `GORSE_PROBE_SERVER_VERSION`.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        Ok(Self {
+            endpoints: var("GORSE_ENTRY_POINT")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|endpoint| !endpoint.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            api_key: var("GORSE_API_KEY").map(ApiKey::from),
+            api_key_file: var("GORSE_API_KEY_FILE").map(PathBuf::from),
+            timeout_ms: parse_var("GORSE_TIMEOUT_MS", var("GORSE_TIMEOUT_MS"))?,
+            connect_timeout_ms: parse_var(
+                "GORSE_CONNECT_TIMEOUT_MS",
+                var("GORSE_CONNECT_TIMEOUT_MS"),
+            )?,
+            max_retries: parse_var("GORSE_MAX_RETRIES", var("GORSE_MAX_RETRIES"))?,
+            retry_backoff_ms: parse_var("GORSE_RETRY_BACKOFF_MS", var("GORSE_RETRY_BACKOFF_MS"))?,
+            batch_size: parse_var("GORSE_BATCH_SIZE", var("GORSE_BATCH_SIZE"))?,
+            server_version: parse_var("GORSE_SERVER_VERSION", var("GORSE_SERVER_VERSION"))?,
+            probe_server_version: parse_var(
+                "GORSE_PROBE_SERVER_VERSION",
+                var("GORSE_PROBE_SERVER_VERSION"),
+            )?,
+            ..Default::default()
+        })
+    }
+
+    /// Reads the configuration from a file, in the format given by its
+    /// extension: `.toml`, `.json`, `.yaml` or `.yml`.
+    #[cfg(feature = "config")]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| Error::Config(format!("cannot read {}: {}", path.display(), err)))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            Some("yaml" | "yml") => Self::from_yaml(&content),
+            _ => Err(Error::Config(format!(
+                "unknown configuration format: {}",
+                path.display()
+            ))),
+        }
+    }
+
+    #[cfg(feature = "config")]
+    pub fn from_toml(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|err| Error::Config(err.to_string()))
+    }
+
+    #[cfg(feature = "config")]
+    pub fn from_json(content: &str) -> Result<Self> {
+        serde_json::from_str(content).map_err(|err| Error::Config(err.to_string()))
+    }
+
+    #[cfg(feature = "config")]
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        serde_yaml_ng::from_str(content).map_err(|err| Error::Config(err.to_string()))
+    }
+
+    /// Checks that the configuration describes a usable client.
+    pub fn validate(&self) -> Result<()> {
+        let endpoints: Vec<_> = self.entry_point.iter().chain(&self.endpoints).collect();
+        if endpoints.is_empty() {
+            return invalid("at least one endpoint is required");
+        }
+        for endpoint in endpoints {
+            match reqwest::Url::parse(endpoint) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+                _ => return invalid(format!("invalid endpoint: {}", endpoint)),
+            }
+        }
+        if self.api_key.is_some() && self.api_key_file.is_some() {
+            return invalid("api_key and api_key_file are exclusive");
+        }
+        if self.timeout_ms == Some(0) || self.connect_timeout_ms == Some(0) {
+            return invalid("timeouts must be positive");
+        }
+        if self.failure_threshold == Some(0) {
+            return invalid("failure_threshold must be positive");
+        }
+        if self.probe_interval_ms == Some(0) {
+            return invalid("probe_interval_ms must be positive");
+        }
+        if self.batch_size == Some(0) {
+            return invalid("batch_size must be positive");
+        }
+        #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+        if self.client_certificate_file.is_some() != self.client_key_file.is_some() {
+            return invalid("client_certificate_file and client_key_file go together");
+        }
+        Ok(())
+    }
+
+    /// Validates the configuration and returns a builder configured with it,
+    /// to which more settings can be added.
+    pub fn builder(&self) -> Result<GorseBuilder> {
+        self.validate()?;
+        let mut builder =
+            Gorse::builder().endpoints(self.entry_point.iter().chain(&self.endpoints).cloned());
+        if let Some(api_key) = &self.api_key {
+            builder = builder.api_key(api_key.expose());
+        }
+        if let Some(path) = &self.api_key_file {
+            builder = builder.credentials(FileCredentials::new(path));
+        }
+        if let Some(timeout) = self.timeout_ms {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+        if let Some(connect_timeout) = self.connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::from_millis(connect_timeout));
+        }
+        if let Some(strategy) = self.strategy {
+            builder = builder.strategy(strategy);
+        }
+        if let Some(failure_threshold) = self.failure_threshold {
+            builder = builder.failure_threshold(failure_threshold);
+        }
+        if let Some(probe_interval) = self.probe_interval_ms {
+            builder = builder.probe_interval(Duration::from_millis(probe_interval));
+        }
+        if let Some(max_retries) = self.max_retries {
+            builder = builder.max_retries(max_retries);
+        }
+        if let Some(retry_backoff) = self.retry_backoff_ms {
+            builder = builder.retry_backoff(Duration::from_millis(retry_backoff));
+        }
+        if let Some(batch_size) = self.batch_size {
+            builder = builder.batch_size(batch_size);
+        }
+        if let Some(server_version) = self.server_version {
+            builder = builder.server_version(server_version);
+        }
+        if let Some(probe_server_version) = self.probe_server_version {
+            builder = builder.probe_server_version(probe_server_version);
+        }
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        {
+            if let Some(compression) = self.compression {
+                builder = builder.compression(compression);
+            }
+            if let Some(threshold) = self.compression_threshold {
+                builder = builder.compression_threshold(threshold);
+            }
+        }
+        #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+        {
+            if let Some(path) = &self.ca_bundle_file {
+                builder = builder.ca_bundle_pem(read_file(path)?);
+            }
+            if let (Some(certificate), Some(key)) =
+                (&self.client_certificate_file, &self.client_key_file)
+            {
+                builder = builder.client_identity_pem(read_file(certificate)?, read_file(key)?);
+            }
+        }
+        Ok(builder)
+    }
+
+    pub fn build(&self) -> Result<Gorse> {
+        self.builder()?.build()
+    }
+
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(&self) -> Result<crate::blocking::Gorse> {
+        self.builder()?.build_blocking()
+    }
+}
+
+fn parse_var<T: FromStr>(name: &str, value: Option<String>) -> Result<Option<T>> {
+    value
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| Error::Config(format!("invalid {}: {}", name, value)))
+        })
+        .transpose()
+}
+
+#[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+fn read_file(path: &std::path::Path) -> Result<Vec<u8>> {
+    std::fs::read(path)
+        .map_err(|err| Error::Config(format!("cannot read {}: {}", path.display(), err)))
+}
+
+fn invalid(message: impl Into<String>) -> Result<()> {
+    Err(Error::Config(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_vars() -> Result<()> {
+        let config = GorseConfig::from_vars(|name| match name {
+            "GORSE_ENTRY_POINT" => Some("http://10.0.0.1:8087, http://10.0.0.2:8087".into()),
+            "GORSE_API_KEY" => Some("zhenghaoz".into()),
+            "GORSE_TIMEOUT_MS" => Some("500".into()),
+            "GORSE_SERVER_VERSION" => Some("v0.4.15".into()),
+            "GORSE_MAX_RETRIES" => Some("2".into()),
+            "GORSE_RETRY_BACKOFF_MS" => Some("50".into()),
+            _ => None,
+        })?;
+        assert_eq!(
+            config.endpoints,
+            vec!["http://10.0.0.1:8087", "http://10.0.0.2:8087"]
+        );
+        assert_eq!(config.api_key, Some(ApiKey::new("zhenghaoz")));
+        assert_eq!(config.timeout_ms, Some(500));
+        assert_eq!(config.server_version, Some(ServerVersion::new(0, 4, 15)));
+        assert_eq!(config.max_retries, Some(2));
+        assert_eq!(config.retry_backoff_ms, Some(50));
+        let client = config.build()?;
+        let retry = client.endpoints.retry();
+        assert_eq!(retry.max_retries, 2);
+        assert_eq!(retry.backoff, Duration::from_millis(50));
+
+        let config = GorseConfig::from_vars(|name| match name {
+            "GORSE_BATCH_SIZE" => Some("many".into()),
+            _ => None,
+        });
+        assert!(matches!(config, Err(Error::Config(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(GorseConfig::default().validate().is_err());
+        let config = GorseConfig {
+            entry_point: Some("http://127.0.0.1:8087".into()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        for invalid in [
+            GorseConfig {
+                endpoints: vec!["127.0.0.1:8087".into()],
+                ..config.clone()
+            },
+            GorseConfig {
+                api_key: Some(ApiKey::new("key")),
+                api_key_file: Some("key".into()),
+                ..config.clone()
+            },
+            GorseConfig {
+                batch_size: Some(0),
+                ..config.clone()
+            },
+        ] {
+            assert!(matches!(invalid.validate(), Err(Error::Config(_))));
+        }
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_formats() -> Result<()> {
+        let toml = GorseConfig::from_toml(
+            r#"
+            endpoints = ["http://127.0.0.1:8087"]
+            api_key = "zhenghaoz"
+            strategy = "least_outstanding"
+            max_retries = 2
+            "#,
+        )?;
+        let json = GorseConfig::from_json(
+            r#"{
+                "endpoints": ["http://127.0.0.1:8087"],
+                "api_key": "zhenghaoz",
+                "strategy": "least_outstanding",
+                "max_retries": 2
+            }"#,
+        )?;
+        let yaml = GorseConfig::from_yaml(
+            "endpoints: [\"http://127.0.0.1:8087\"]\napi_key: zhenghaoz\nstrategy: least_outstanding\nmax_retries: 2\n",
+        )?;
+        for config in [toml, json, yaml] {
+            assert_eq!(config.endpoints, vec!["http://127.0.0.1:8087"]);
+            assert_eq!(config.api_key, Some(ApiKey::new("zhenghaoz")));
+            assert_eq!(config.strategy, Some(LoadBalanceStrategy::LeastOutstanding));
+            assert_eq!(config.max_retries, Some(2));
+        }
+        assert!(matches!(
+            GorseConfig::from_toml("timeout = 1"),
+            Err(Error::Config(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+/// An API key, redacted when debug printed.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(key.into())
+    }
+
+    /// Returns the key in plain text.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ApiKey(<redacted>)")
+    }
+}
+
+impl From<String> for ApiKey {
+    fn from(key: String) -> Self {
+        Self(key)
+    }
+}
+
+impl From<&str> for ApiKey {
+    fn from(key: &str) -> Self {
+        Self(key.to_string())
+    }
+}
+
+/// Source of the API key, asked for the key before each request so that keys
+/// can be rotated without rebuilding clients.
+///
+/// ```no_run
+/// use gorse_rs::{FileCredentials, Gorse};
+///
+/// let client = Gorse::builder()
+///     .entry_point("http://127.0.0.1:8087")
+///     .credentials(FileCredentials::new("/run/secrets/gorse_api_key"))
+///     .build()?;
+/// # Ok::<(), gorse_rs::Error>(())
+/// ```
+pub trait CredentialsProvider: Send + Sync {
+    fn api_key(&self) -> Result<ApiKey>;
+}
+
+impl<P: CredentialsProvider + ?Sized> CredentialsProvider for Arc<P> {
+    fn api_key(&self) -> Result<ApiKey> {
+        (**self).api_key()
+    }
+}
+
+/// A fixed API key.
+#[derive(Debug, Clone, Default)]
+pub struct StaticCredentials(pub ApiKey);
+
+impl CredentialsProvider for StaticCredentials {
+    fn api_key(&self) -> Result<ApiKey> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Reads the API key from an environment variable on each request.
+#[derive(Debug, Clone)]
+pub struct EnvCredentials {
+    name: String,
+}
+
+impl EnvCredentials {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+impl CredentialsProvider for EnvCredentials {
+    fn api_key(&self) -> Result<ApiKey> {
+        std::env::var(&self.name)
+            .map(ApiKey)
+            .map_err(|err| Error::Config(format!("cannot read {}: {}", self.name, err)))
+    }
+}
+
+/// Reads the API key from a file, e.g. a mounted secret, and reads it again
+/// whenever the file is modified. Surrounding whitespace is trimmed.
+#[derive(Debug)]
+pub struct FileCredentials {
+    path: PathBuf,
+    cache: Mutex<Option<(SystemTime, ApiKey)>>,
+}
+
+impl FileCredentials {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    fn read(&self) -> std::io::Result<ApiKey> {
+        let modified = std::fs::metadata(&self.path)?.modified()?;
+        let mut cache = self.cache.lock().unwrap();
+        if let Some((cached, key)) = cache.as_ref() {
+            if *cached == modified {
+                return Ok(key.clone());
+            }
+        }
+        let key = ApiKey::new(std::fs::read_to_string(&self.path)?.trim());
+        *cache = Some((modified, key.clone()));
+        Ok(key)
+    }
+}
+
+impl CredentialsProvider for FileCredentials {
+    fn api_key(&self) -> Result<ApiKey> {
+        self.read()
+            .map_err(|err| Error::Config(format!("cannot read {}: {}", self.path.display(), err)))
+    }
+}
+
+/// Asks a callback for the API key, e.g. to fetch it from a secret manager.
+/// The callback is called before each request and should cache the key.
+pub struct CallbackCredentials<F>(pub F);
+
+impl<F> fmt::Debug for CallbackCredentials<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CallbackCredentials")
+    }
+}
+
+impl<F: Fn() -> Result<ApiKey> + Send + Sync> CredentialsProvider for CallbackCredentials<F> {
+    fn api_key(&self) -> Result<ApiKey> {
+        (self.0)()
+    }
+}
+
+/// Credentials provider registered on a client.
+#[derive(Clone)]
+pub(crate) struct Credentials(pub(crate) Arc<dyn CredentialsProvider>);
+
+impl Default for Credentials {
+    fn default() -> Self {
+        Self(Arc::new(StaticCredentials::default()))
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Credentials(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacted() {
+        let key = ApiKey::new("zhenghaoz");
+        assert_eq!(key.expose(), "zhenghaoz");
+        assert!(!format!("{:?}", key).contains("zhenghaoz"));
+        assert!(!format!("{:?}", StaticCredentials(key)).contains("zhenghaoz"));
+    }
+
+    #[test]
+    fn test_file_credentials() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("gorse_api_key_{}", std::process::id()));
+        std::fs::write(&path, "first\n").unwrap();
+        let credentials = FileCredentials::new(&path);
+        assert_eq!(credentials.api_key()?.expose(), "first");
+
+        // Set the modification time explicitly, since writes within the
+        // timestamp resolution of the file system are not detected.
+        std::fs::write(&path, "second").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(credentials.api_key()?.expose(), "second");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(credentials.api_key(), Err(Error::Config(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_callback_credentials() -> Result<()> {
+        let credentials = CallbackCredentials(|| Ok(ApiKey::new("rotated")));
+        assert_eq!(credentials.api_key()?.expose(), "rotated");
+        assert!(matches!(
+            EnvCredentials::new("GORSE_TEST_MISSING_API_KEY").api_key(),
+            Err(Error::Config(_))
+        ));
+        Ok(())
+    }
+}
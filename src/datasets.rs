@@ -0,0 +1,383 @@
+//! Loaders of public datasets from local files into [`Item`]s and
+//! [`Feedback`], ready for [`Gorse::insert_items`](crate::Gorse::insert_items)
+//! and [`Gorse::insert_feedback`](crate::Gorse::insert_feedback).
+//!
+//! ```no_run
+//! # async fn run(client: gorse_rs::Gorse) -> Result<(), gorse_rs::Error> {
+//! let dataset = gorse_rs::datasets::load_movielens("ml-100k", "rating")?;
+//! client.insert_items(&dataset.items).await?;
+//! client.insert_feedback(&dataset.feedback).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Ratings are the feedback value and genres are the item categories. Items
+//! are commented with the movie title, and timestamps are RFC 3339.
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{timestamp, Error, Feedback, Item, Result};
+
+/// Genres of the flags of `u.item` in MovieLens 100K, in order.
+const U_ITEM_GENRES: [&str; 19] = [
+    "unknown",
+    "Action",
+    "Adventure",
+    "Animation",
+    "Children's",
+    "Comedy",
+    "Crime",
+    "Documentary",
+    "Drama",
+    "Fantasy",
+    "Film-Noir",
+    "Horror",
+    "Musical",
+    "Mystery",
+    "Romance",
+    "Sci-Fi",
+    "Thriller",
+    "War",
+    "Western",
+];
+
+/// Items and feedback of a dataset.
+#[derive(Debug, Default, PartialEq)]
+pub struct Dataset {
+    pub items: Vec<Item>,
+    pub feedback: Vec<Feedback>,
+}
+
+/// Loads a MovieLens dataset from its extracted directory, in any of the
+/// layouts of the GroupLens releases:
+/// - `u.data` and `u.item`, e.g. ml-100k;
+/// - `ratings.dat` and `movies.dat`, e.g. ml-1m and ml-10m;
+/// - `ratings.csv` and `movies.csv`, e.g. ml-latest and ml-25m.
+///
+/// Ratings are feedback of type `feedback_type`.
+pub fn load_movielens(dir: impl AsRef<Path>, feedback_type: &str) -> Result<Dataset> {
+    let dir = dir.as_ref();
+    let open = |name: &str| std::fs::File::open(dir.join(name));
+    if dir.join("u.data").exists() {
+        Ok(Dataset {
+            items: read_u_item(open("u.item")?)?,
+            feedback: read_u_data(open("u.data")?, feedback_type)?,
+        })
+    } else if dir.join("ratings.dat").exists() {
+        Ok(Dataset {
+            items: read_movies_dat(open("movies.dat")?)?,
+            feedback: read_ratings_dat(open("ratings.dat")?, feedback_type)?,
+        })
+    } else if dir.join("ratings.csv").exists() {
+        Ok(Dataset {
+            items: read_movies_csv(open("movies.csv")?)?,
+            feedback: read_ratings_csv(open("ratings.csv")?, feedback_type)?,
+        })
+    } else {
+        Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no MovieLens ratings in {}", dir.display()),
+        )))
+    }
+}
+
+/// Reads MovieLens 100K ratings, `user id | item id | rating | timestamp`
+/// separated by tabs.
+pub fn read_u_data(reader: impl Read, feedback_type: &str) -> Result<Vec<Feedback>> {
+    read_lines(reader, |line| {
+        let fields: Vec<&str> = line.split('\t').collect();
+        rating(&fields, feedback_type).map(Some)
+    })
+}
+
+/// Reads MovieLens 100K movies, `movie id | title | release date | video
+/// release date | IMDb URL` and 19 genre flags separated by `|`.
+pub fn read_u_item(reader: impl Read) -> Result<Vec<Item>> {
+    read_lines(reader, |line| {
+        let fields: Vec<&str> = line.split('|').collect();
+        let [item_id, title, release_date, _, _, flags @ ..] = fields.as_slice() else {
+            return Err(format!("expected 24 fields, found {}", fields.len()));
+        };
+        if flags.len() != U_ITEM_GENRES.len() {
+            return Err(format!("expected 24 fields, found {}", fields.len()));
+        }
+        let categories = flags
+            .iter()
+            .zip(U_ITEM_GENRES)
+            .filter(|&(flag, genre)| *flag == "1" && genre != "unknown")
+            .map(|(_, genre)| genre.to_string())
+            .collect();
+        Ok(Some(movie(
+            item_id,
+            title,
+            categories,
+            parse_release_date(release_date).unwrap_or_default(),
+        )))
+    })
+}
+
+/// Reads MovieLens 1M or 10M ratings, `UserID::MovieID::Rating::Timestamp`.
+pub fn read_ratings_dat(reader: impl Read, feedback_type: &str) -> Result<Vec<Feedback>> {
+    read_lines(reader, |line| {
+        let fields: Vec<&str> = line.split("::").collect();
+        rating(&fields, feedback_type).map(Some)
+    })
+}
+
+/// Reads MovieLens 1M or 10M movies, `MovieID::Title::Genres` with genres
+/// separated by `|`.
+pub fn read_movies_dat(reader: impl Read) -> Result<Vec<Item>> {
+    read_lines(reader, |line| {
+        let fields: Vec<&str> = line.split("::").collect();
+        let [item_id, title, genres] = fields.as_slice() else {
+            return Err(format!("expected 3 fields, found {}", fields.len()));
+        };
+        Ok(Some(movie(
+            item_id,
+            title,
+            genres_list(genres),
+            String::new(),
+        )))
+    })
+}
+
+/// Reads MovieLens ratings in CSV, `userId,movieId,rating,timestamp` with
+/// a header.
+pub fn read_ratings_csv(reader: impl Read, feedback_type: &str) -> Result<Vec<Feedback>> {
+    read_lines(reader, |line| {
+        if line.starts_with("userId,") {
+            return Ok(None);
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        rating(&fields, feedback_type).map(Some)
+    })
+}
+
+/// Reads MovieLens movies in CSV, `movieId,title,genres` with a header.
+/// Titles are quoted when they contain commas.
+pub fn read_movies_csv(reader: impl Read) -> Result<Vec<Item>> {
+    read_lines(reader, |line| {
+        if line.starts_with("movieId,") {
+            return Ok(None);
+        }
+        // Only titles contain commas, so the ids and genres are at the ends.
+        let (Some((item_id, rest)), Some((_, genres))) =
+            (line.split_once(','), line.rsplit_once(','))
+        else {
+            return Err("expected 3 fields".to_string());
+        };
+        let Some(title) = rest
+            .strip_suffix(genres)
+            .and_then(|rest| rest.strip_suffix(','))
+        else {
+            return Err("expected 3 fields".to_string());
+        };
+        let title = match title
+            .strip_prefix('"')
+            .and_then(|title| title.strip_suffix('"'))
+        {
+            Some(title) => title.replace("\"\"", "\""),
+            None => title.to_string(),
+        };
+        Ok(Some(movie(
+            item_id,
+            &title,
+            genres_list(genres),
+            String::new(),
+        )))
+    })
+}
+
+/// Parses the lines of a file, skipping blank lines and lines parsed as
+/// `None`. Files that are not UTF-8, e.g. the Latin-1 movies of MovieLens
+/// 100K and 1M, are read as Latin-1.
+fn read_lines<T>(
+    reader: impl Read,
+    mut parse: impl FnMut(&str) -> std::result::Result<Option<T>, String>,
+) -> Result<Vec<T>> {
+    let mut reader = BufReader::new(reader);
+    let mut records = Vec::new();
+    let mut buffer = Vec::new();
+    let mut row = 0;
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            return Ok(records);
+        }
+        row += 1;
+        let line = match std::str::from_utf8(&buffer) {
+            Ok(line) => line.to_string(),
+            Err(_) => buffer.iter().map(|&byte| byte as char).collect(),
+        };
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse(line) {
+            Ok(Some(record)) => records.push(record),
+            Ok(None) => {}
+            Err(message) => return Err(Error::InvalidRecord { row, message }),
+        }
+    }
+}
+
+/// Parses `user, item, rating, timestamp` fields.
+fn rating(fields: &[&str], feedback_type: &str) -> std::result::Result<Feedback, String> {
+    let [user_id, item_id, value, timestamp] = fields else {
+        return Err(format!("expected 4 fields, found {}", fields.len()));
+    };
+    let value = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid rating: {:?}", value))?;
+    let timestamp = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid timestamp: {:?}", timestamp))?;
+    Ok(Feedback {
+        feedback_type: feedback_type.to_string(),
+        user_id: user_id.trim().to_string(),
+        item_id: item_id.trim().to_string(),
+        value,
+        timestamp: timestamp::format(timestamp),
+    })
+}
+
+fn movie(item_id: &str, title: &str, categories: Vec<String>, timestamp: String) -> Item {
+    Item {
+        item_id: item_id.trim().to_string(),
+        is_hidden: false,
+        labels: Value::Null,
+        categories,
+        timestamp,
+        comment: title.to_string(),
+    }
+}
+
+fn genres_list(genres: &str) -> Vec<String> {
+    genres
+        .split('|')
+        .filter(|genre| !genre.is_empty() && *genre != "(no genres listed)")
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses release dates of MovieLens 100K, e.g. `01-Jan-1995`.
+fn parse_release_date(date: &str) -> Option<String> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let mut parts = date.split('-');
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    Some(timestamp::format(
+        timestamp::days_from_civil(year, month, day) * 86400,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback(user_id: &str, item_id: &str, value: f64, timestamp: &str) -> Feedback {
+        Feedback {
+            feedback_type: "rating".into(),
+            user_id: user_id.into(),
+            item_id: item_id.into(),
+            value,
+            timestamp: timestamp.into(),
+        }
+    }
+
+    #[test]
+    fn test_release_date() {
+        assert_eq!(
+            parse_release_date("29-Feb-2000").as_deref(),
+            Some("2000-02-29T00:00:00Z")
+        );
+        assert_eq!(parse_release_date(""), None);
+    }
+
+    #[test]
+    fn test_ratings() -> Result<()> {
+        let expected = vec![
+            feedback("196", "242", 3.0, "1997-12-04T15:55:49Z"),
+            feedback("1", "31", 2.5, "2009-12-14T02:52:24Z"),
+        ];
+        let u_data = "196\t242\t3\t881250949\n1\t31\t2.5\t1260759144\n";
+        assert_eq!(read_u_data(u_data.as_bytes(), "rating")?, expected);
+        let dat = "196::242::3::881250949\r\n1::31::2.5::1260759144\r\n";
+        assert_eq!(read_ratings_dat(dat.as_bytes(), "rating")?, expected);
+        let csv = "userId,movieId,rating,timestamp\n196,242,3.0,881250949\n1,31,2.5,1260759144";
+        assert_eq!(read_ratings_csv(csv.as_bytes(), "rating")?, expected);
+
+        assert!(matches!(
+            read_u_data("196\t242\t3\n".as_bytes(), "rating"),
+            Err(Error::InvalidRecord { row: 1, .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_movies() -> Result<()> {
+        let csv = "movieId,title,genres\n\
+                   1,Toy Story (1995),Adventure|Animation\n\
+                   11,\"American President, The (1995)\",Comedy|Drama|Romance\n\
+                   9999,\"Quote \"\"Me\"\" (2000)\",(no genres listed)\n";
+        let items = read_movies_csv(csv.as_bytes())?;
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].categories, vec!["Adventure", "Animation"]);
+        assert_eq!(items[1].item_id, "11");
+        assert_eq!(items[1].comment, "American President, The (1995)");
+        assert_eq!(items[2].comment, "Quote \"Me\" (2000)");
+        assert!(items[2].categories.is_empty());
+
+        // MovieLens 1M movies are Latin-1.
+        let dat = b"73::Mis\xe9rables, Les (1995)::Drama|Musical\n";
+        let items = read_movies_dat(&dat[..])?;
+        assert_eq!(items[0].comment, "Misérables, Les (1995)");
+        assert_eq!(items[0].categories, vec!["Drama", "Musical"]);
+
+        let u_item =
+            "1|Toy Story (1995)|01-Jan-1995||http://us.imdb.com/M/title-exact?Toy%20Story%20(1995)\
+                      |0|0|0|1|1|1|0|0|0|0|0|0|0|0|0|0|0|0|0\n";
+        let items = read_u_item(u_item.as_bytes())?;
+        assert_eq!(
+            items[0].categories,
+            vec!["Animation", "Children's", "Comedy"]
+        );
+        assert_eq!(items[0].timestamp, "1995-01-01T00:00:00Z");
+        assert!(read_u_item("1|Toy Story|01-Jan-1995\n".as_bytes()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_movielens() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("gorse-movielens-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join("ratings.csv"),
+            "userId,movieId,rating,timestamp\n1,1,4.0,0\n",
+        )?;
+        std::fs::write(
+            dir.join("movies.csv"),
+            "movieId,title,genres\n1,Toy Story,Comedy\n",
+        )?;
+        let dataset = load_movielens(&dir, "rating");
+        std::fs::remove_dir_all(&dir)?;
+        let dataset = dataset?;
+        assert_eq!(dataset.items.len(), 1);
+        assert_eq!(
+            dataset.feedback,
+            vec![feedback("1", "1", 4.0, "1970-01-01T00:00:00Z")]
+        );
+
+        assert!(matches!(load_movielens(&dir, "rating"), Err(Error::Io(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// Timeout applied to health probes of unhealthy endpoints.
+pub(crate) const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Retries of reads that failed on every endpoint, after an exponential
+/// backoff.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max_retries: u32,
+    /// Delay before the first retry, doubled before each next one.
+    pub(crate) backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before a retry, counted from 1.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(1 << retry.saturating_sub(1).min(16))
+    }
+}
+
+/// Strategy used to pick an endpoint when a client has several Gorse servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadBalanceStrategy {
+    /// Cycle through healthy endpoints in order.
+    #[default]
+    RoundRobin,
+    /// Pick a healthy endpoint uniformly at random.
+    Random,
+    /// Pick the healthy endpoint with the fewest requests in flight.
+    LeastOutstanding,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    consecutive_failures: AtomicU32,
+    outstanding: AtomicUsize,
+    /// Last time the endpoint was marked unhealthy or probed, `None` while healthy.
+    unhealthy_since: Mutex<Option<Instant>>,
+}
+
+/// Set of Gorse server endpoints shared by the clones of a client.
+#[derive(Debug)]
+pub(crate) struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    strategy: LoadBalanceStrategy,
+    failure_threshold: u32,
+    probe_interval: Duration,
+    retry: RetryPolicy,
+    next: AtomicUsize,
+    random: RandomState,
+}
+
+impl EndpointPool {
+    /// Creates a pool from a non-empty list of endpoints, appending a trailing
+    /// slash to each of them if missing.
+    pub(crate) fn new(
+        urls: Vec<String>,
+        strategy: LoadBalanceStrategy,
+        failure_threshold: u32,
+        probe_interval: Duration,
+        retry: RetryPolicy,
+    ) -> Self {
+        let endpoints = urls
+            .into_iter()
+            .map(|mut url| {
+                if !url.ends_with('/') {
+                    url.push('/');
+                }
+                Endpoint {
+                    url,
+                    consecutive_failures: AtomicU32::new(0),
+                    outstanding: AtomicUsize::new(0),
+                    unhealthy_since: Mutex::new(None),
+                }
+            })
+            .collect();
+        Self {
+            endpoints,
+            strategy,
+            failure_threshold: failure_threshold.max(1),
+            probe_interval,
+            retry,
+            next: AtomicUsize::new(0),
+            random: RandomState::new(),
+        }
+    }
+
+    pub(crate) fn url(&self, index: usize) -> &str {
+        &self.endpoints[index].url
+    }
+
+    pub(crate) fn retry(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    fn is_healthy(&self, index: usize) -> bool {
+        self.endpoints[index]
+            .unhealthy_since
+            .lock()
+            .unwrap()
+            .is_none()
+    }
+
+    /// Returns endpoint indices in the order they should be tried. Healthy
+    /// endpoints come first, ordered by the strategy. Unhealthy endpoints are
+    /// appended so that requests are still attempted when every endpoint is down.
+    pub(crate) fn candidates(&self) -> Vec<usize> {
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|&i| self.is_healthy(i));
+        if !healthy.is_empty() {
+            match self.strategy {
+                LoadBalanceStrategy::RoundRobin => {
+                    let start = self.next.fetch_add(1, Ordering::Relaxed) % healthy.len();
+                    healthy.rotate_left(start);
+                }
+                LoadBalanceStrategy::Random => {
+                    let mut hasher = self.random.build_hasher();
+                    hasher.write_usize(self.next.fetch_add(1, Ordering::Relaxed));
+                    let start = hasher.finish() as usize % healthy.len();
+                    healthy.rotate_left(start);
+                }
+                LoadBalanceStrategy::LeastOutstanding => {
+                    healthy.sort_by_key(|&i| self.endpoints[i].outstanding.load(Ordering::Relaxed));
+                }
+            }
+        }
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    /// Returns unhealthy endpoints whose probe interval has elapsed, resetting
+    /// their probe timer so that concurrent requests do not probe them twice.
+    pub(crate) fn due_for_probe(&self) -> Vec<usize> {
+        let now = Instant::now();
+        (0..self.endpoints.len())
+            .filter(|&i| {
+                let mut since = self.endpoints[i].unhealthy_since.lock().unwrap();
+                match *since {
+                    Some(t) if now.duration_since(t) >= self.probe_interval => {
+                        *since = Some(now);
+                        true
+                    }
+                    _ => false,
+                }
+            })
+            .collect()
+    }
+
+    /// Tracks a request in flight on an endpoint until the guard is dropped.
+    pub(crate) fn begin(&self, index: usize) -> Outstanding<'_> {
+        self.endpoints[index]
+            .outstanding
+            .fetch_add(1, Ordering::Relaxed);
+        Outstanding {
+            endpoint: &self.endpoints[index],
+        }
+    }
+
+    pub(crate) fn mark_success(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        endpoint.consecutive_failures.store(0, Ordering::Relaxed);
+        *endpoint.unhealthy_since.lock().unwrap() = None;
+    }
+
+    pub(crate) fn mark_failure(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        let failures = endpoint
+            .consecutive_failures
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        if failures >= self.failure_threshold {
+            let mut since = endpoint.unhealthy_since.lock().unwrap();
+            if since.is_none() {
+                *since = Some(Instant::now());
+            }
+        }
+    }
+}
+
+pub(crate) struct Outstanding<'a> {
+    endpoint: &'a Endpoint,
+}
+
+impl Drop for Outstanding<'_> {
+    fn drop(&mut self) {
+        self.endpoint.outstanding.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(strategy: LoadBalanceStrategy) -> EndpointPool {
+        EndpointPool::new(
+            vec!["http://a".into(), "http://b/".into(), "http://c".into()],
+            strategy,
+            2,
+            Duration::ZERO,
+            RetryPolicy {
+                max_retries: 0,
+                backoff: Duration::ZERO,
+            },
+        )
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let pool = pool(LoadBalanceStrategy::RoundRobin);
+        assert_eq!(pool.url(0), "http://a/");
+        assert_eq!(pool.url(1), "http://b/");
+        assert_eq!(pool.candidates(), vec![0, 1, 2]);
+        assert_eq!(pool.candidates(), vec![1, 2, 0]);
+        assert_eq!(pool.candidates(), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn test_least_outstanding() {
+        let pool = pool(LoadBalanceStrategy::LeastOutstanding);
+        let _a = pool.begin(0);
+        let b = pool.begin(1);
+        assert_eq!(pool.candidates()[0], 2);
+        drop(b);
+        assert_eq!(pool.candidates()[0], 1);
+    }
+
+    #[test]
+    fn test_unhealthy_endpoint() {
+        let pool = pool(LoadBalanceStrategy::RoundRobin);
+        pool.mark_failure(0);
+        assert_eq!(pool.candidates(), vec![0, 1, 2]);
+        pool.mark_failure(0);
+        assert_eq!(pool.candidates(), vec![2, 1, 0]);
+        assert_eq!(pool.due_for_probe(), vec![0]);
+        pool.mark_success(0);
+        assert!(pool.due_for_probe().is_empty());
+        assert_eq!(pool.candidates(), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = RetryPolicy {
+            max_retries: 3,
+            backoff: Duration::from_millis(100),
+        };
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(3), Duration::from_millis(400));
+        assert_eq!(retry.delay(u32::MAX), Duration::from_millis(100 << 16));
+    }
+}
@@ -0,0 +1,386 @@
+//! Offline evaluation of the recommendations of a Gorse server against
+//! held-out feedback.
+//!
+//! ```no_run
+//! # async fn run(client: gorse_rs::Gorse, train: Vec<gorse_rs::Feedback>, test: Vec<gorse_rs::Feedback>) -> Result<(), gorse_rs::Error> {
+//! use gorse_rs::evaluation::EvaluationOptions;
+//!
+//! let options = EvaluationOptions {
+//!     k: 10,
+//!     concurrency: 4,
+//!     requests_per_second: Some(50.0),
+//! };
+//! let report = client.evaluate(&train, &test, &options).await?;
+//! println!("{}", report);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{Feedback, RecommendOptions, Result};
+
+/// Options of [`Gorse::evaluate`](crate::Gorse::evaluate).
+#[derive(Debug, Clone)]
+pub struct EvaluationOptions {
+    /// Number of items recommended to each user, 10 by default.
+    pub k: usize,
+    /// Maximum number of concurrent recommendation requests, 8 by default.
+    pub concurrency: usize,
+    /// Maximum rate of recommendation requests, unlimited by default.
+    pub requests_per_second: Option<f64>,
+}
+
+impl Default for EvaluationOptions {
+    fn default() -> Self {
+        Self {
+            k: 10,
+            concurrency: 8,
+            requests_per_second: None,
+        }
+    }
+}
+
+impl EvaluationOptions {
+    /// Delay after the start of an evaluation before the `index`th request.
+    fn delay(&self, index: usize) -> Duration {
+        match self.requests_per_second {
+            Some(rate) if rate > 0.0 => Duration::from_secs_f64(index as f64 / rate),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Quality of recommendations, averaged over the users with test feedback.
+/// Reports of the same recommendations are identical.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvaluationReport {
+    pub k: usize,
+    /// Users with test feedback.
+    pub users: usize,
+    /// Share of the recommended items that are in the test feedback.
+    pub precision: f64,
+    /// Share of the test feedback that is recommended, up to `k` items.
+    pub recall: f64,
+    /// Normalized discounted cumulative gain.
+    pub ndcg: f64,
+    /// Mean average precision.
+    pub map: f64,
+    /// Share of the users recommended at least one item of their test
+    /// feedback.
+    pub hit_rate: f64,
+    /// Share of the items of the train and test feedback recommended to any
+    /// user.
+    pub coverage: f64,
+    /// Mean self-information of the recommended items, `-log2` of the share
+    /// of train users who gave them feedback, smoothed for unseen items.
+    /// Higher is less popular.
+    pub novelty: f64,
+}
+
+impl fmt::Display for EvaluationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "users         {}", self.users)?;
+        writeln!(f, "precision@{:<3} {:.4}", self.k, self.precision)?;
+        writeln!(f, "recall@{:<6} {:.4}", self.k, self.recall)?;
+        writeln!(f, "ndcg@{:<8} {:.4}", self.k, self.ndcg)?;
+        writeln!(f, "map@{:<9} {:.4}", self.k, self.map)?;
+        writeln!(f, "hit_rate@{:<4} {:.4}", self.k, self.hit_rate)?;
+        writeln!(f, "coverage      {:.4}", self.coverage)?;
+        write!(f, "novelty       {:.4}", self.novelty)
+    }
+}
+
+/// Evaluates the top `k` recommended items of each user, e.g. computed
+/// offline, against the test feedback. Users without test feedback are not
+/// evaluated.
+pub fn evaluate(
+    recommendations: &HashMap<String, Vec<String>>,
+    train: &[Feedback],
+    test: &[Feedback],
+    k: usize,
+) -> EvaluationReport {
+    let relevant = items_by_user(test);
+    let mut train_users: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for feedback in train {
+        train_users
+            .entry(&feedback.item_id)
+            .or_default()
+            .insert(&feedback.user_id);
+    }
+    let user_count = train
+        .iter()
+        .map(|feedback| &feedback.user_id)
+        .collect::<HashSet<_>>()
+        .len();
+    let catalog: HashSet<&str> = train
+        .iter()
+        .chain(test)
+        .map(|feedback| feedback.item_id.as_str())
+        .collect();
+
+    let mut sums = [0.0; 5];
+    let mut recommended_items = HashSet::new();
+    let (mut novelty, mut recommended_count) = (0.0, 0);
+    for (user_id, relevant) in &relevant {
+        let recommended = recommendations
+            .get(*user_id)
+            .map_or(&[][..], |items| &items[..items.len().min(k)]);
+        for (i, metric) in user_metrics(recommended, relevant, k)
+            .into_iter()
+            .enumerate()
+        {
+            sums[i] += metric;
+        }
+        for item_id in recommended {
+            recommended_items.insert(item_id.as_str());
+            let users = train_users.get(item_id.as_str()).map_or(0, HashSet::len);
+            novelty -= ((users + 1) as f64 / (user_count + 1) as f64).log2();
+            recommended_count += 1;
+        }
+    }
+    let mean = |sum: f64| match relevant.len() {
+        0 => 0.0,
+        users => sum / users as f64,
+    };
+    let [precision, recall, ndcg, map, hit_rate] = sums.map(mean);
+    EvaluationReport {
+        k,
+        users: relevant.len(),
+        precision,
+        recall,
+        ndcg,
+        map,
+        hit_rate,
+        coverage: match catalog.len() {
+            0 => 0.0,
+            items => recommended_items.intersection(&catalog).count() as f64 / items as f64,
+        },
+        novelty: match recommended_count {
+            0 => 0.0,
+            count => novelty / count as f64,
+        },
+    }
+}
+
+/// Items of the feedback of each user, ordered by user for reproducible
+/// sums.
+fn items_by_user(feedback: &[Feedback]) -> BTreeMap<&str, BTreeSet<&str>> {
+    let mut items: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for feedback in feedback {
+        items
+            .entry(&feedback.user_id)
+            .or_default()
+            .insert(&feedback.item_id);
+    }
+    items
+}
+
+/// Precision, recall, NDCG, average precision and hit of the recommended
+/// items of a user.
+fn user_metrics(recommended: &[String], relevant: &BTreeSet<&str>, k: usize) -> [f64; 5] {
+    let ideal = relevant.len().min(k);
+    let (mut hits, mut dcg, mut precisions) = (0, 0.0, 0.0);
+    let mut seen = HashSet::new();
+    for (rank, item_id) in recommended.iter().enumerate() {
+        if relevant.contains(item_id.as_str()) && seen.insert(item_id) {
+            hits += 1;
+            dcg += 1.0 / (rank as f64 + 2.0).log2();
+            precisions += hits as f64 / (rank + 1) as f64;
+        }
+    }
+    let idcg: f64 = (0..ideal)
+        .map(|rank| 1.0 / (rank as f64 + 2.0).log2())
+        .sum();
+    let ratio = |value: f64, total: f64| if total > 0.0 { value / total } else { 0.0 };
+    [
+        ratio(hits as f64, k as f64),
+        ratio(hits as f64, relevant.len() as f64),
+        ratio(dcg, idcg),
+        ratio(precisions, ideal as f64),
+        if hits > 0 { 1.0 } else { 0.0 },
+    ]
+}
+
+impl crate::Gorse {
+    /// Evaluates the recommendations of the server to the users of the test
+    /// feedback, e.g. held out of the feedback inserted for training.
+    /// Recommendations are requested concurrently, and the first failed
+    /// request fails the evaluation.
+    pub async fn evaluate(
+        &self,
+        train: &[Feedback],
+        test: &[Feedback],
+        options: &EvaluationOptions,
+    ) -> Result<EvaluationReport> {
+        use futures_util::{StreamExt, TryStreamExt};
+
+        let start = tokio::time::Instant::now();
+        let users: Vec<&str> = items_by_user(test).into_keys().collect();
+        let recommendations = futures_util::stream::iter(users.into_iter().enumerate())
+            .map(|(index, user_id)| async move {
+                tokio::time::sleep_until(start + options.delay(index)).await;
+                let items = self
+                    .get_recommend_ids(user_id, RecommendOptions { n: options.k })
+                    .await?;
+                Ok::<_, crate::Error>((user_id.to_string(), items))
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .try_collect()
+            .await?;
+        Ok(evaluate(&recommendations, train, test, options.k))
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl crate::blocking::Gorse {
+    /// Evaluates the recommendations of the server to the users of the test
+    /// feedback, e.g. held out of the feedback inserted for training.
+    /// Recommendations are requested by concurrent threads, and the first
+    /// failed request fails the evaluation.
+    pub fn evaluate(
+        &self,
+        train: &[Feedback],
+        test: &[Feedback],
+        options: &EvaluationOptions,
+    ) -> Result<EvaluationReport> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        let start = std::time::Instant::now();
+        let users: Vec<&str> = items_by_user(test).into_keys().collect();
+        let next = AtomicUsize::new(0);
+        let recommendations = Mutex::new(Ok(HashMap::new()));
+        std::thread::scope(|scope| {
+            for _ in 0..options.concurrency.max(1).min(users.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(user_id) = users.get(index) else {
+                        return;
+                    };
+                    if let Some(delay) = (start + options.delay(index))
+                        .checked_duration_since(std::time::Instant::now())
+                    {
+                        std::thread::sleep(delay);
+                    }
+                    let items = self.get_recommend_ids(user_id, RecommendOptions { n: options.k });
+                    let mut recommendations = recommendations.lock().unwrap();
+                    match (&mut *recommendations, items) {
+                        (Ok(recommendations), Ok(items)) => {
+                            recommendations.insert(user_id.to_string(), items);
+                        }
+                        (Ok(_), Err(err)) => *recommendations = Err(err),
+                        (Err(_), _) => {}
+                    }
+                    if recommendations.is_err() {
+                        // Stop the other threads after their request.
+                        next.store(users.len(), Ordering::Relaxed);
+                        return;
+                    }
+                });
+            }
+        });
+        let recommendations = recommendations.into_inner().unwrap()?;
+        Ok(evaluate(&recommendations, train, test, options.k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+
+    fn feedback(user_id: &str, item_id: &str) -> Feedback {
+        Feedback {
+            feedback_type: "star".into(),
+            user_id: user_id.into(),
+            item_id: item_id.into(),
+            value: 1.0,
+            timestamp: String::new(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn data() -> (Vec<Feedback>, Vec<Feedback>) {
+        let train = vec![feedback("a", "1"), feedback("b", "1"), feedback("b", "2")];
+        let test = vec![feedback("a", "2"), feedback("a", "3"), feedback("b", "4")];
+        (train, test)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let (train, test) = data();
+        let recommendations = HashMap::from([
+            ("a".to_string(), vec!["3".to_string(), "5".to_string()]),
+            ("b".to_string(), vec!["5".to_string(), "6".to_string()]),
+            ("c".to_string(), vec!["1".to_string()]),
+        ]);
+        let report = evaluate(&recommendations, &train, &test, 2);
+        assert_eq!(report.users, 2);
+        // User a hits its first recommendation of 2 relevant items, user b
+        // hits nothing.
+        assert_close(report.precision, 0.25);
+        assert_close(report.recall, 0.25);
+        assert_close(report.ndcg, 0.5 / (1.0 + 1.0 / 3f64.log2()));
+        assert_close(report.map, 0.25);
+        assert_close(report.hit_rate, 0.5);
+        // Only 3 of the items 1 to 4 is recommended to a test user.
+        assert_close(report.coverage, 0.25);
+        // None of the 4 recommended items are in the train feedback of the 2
+        // train users.
+        assert_close(report.novelty, 3f64.log2());
+
+        let empty = evaluate(&HashMap::new(), &[], &[], 10);
+        assert_eq!(empty.users, 0);
+        assert_eq!(empty.precision, 0.0);
+    }
+
+    /// Serves the same recommendation to `requests` requests.
+    fn serve(requests: usize) -> String {
+        test_server::serve(requests, |_, _| test_server::json(r#"["3","4"]"#)).0
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_client() -> Result<()> {
+        let (train, test) = data();
+        let client = crate::Gorse::new(serve(2), "");
+        let options = EvaluationOptions {
+            k: 2,
+            concurrency: 2,
+            requests_per_second: Some(20.0),
+        };
+        let start = std::time::Instant::now();
+        let report = client.evaluate(&train, &test, &options).await?;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(report.users, 2);
+        assert_close(report.hit_rate, 1.0);
+        Ok(())
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_evaluate_blocking() -> Result<()> {
+        let (train, test) = data();
+        let client = crate::blocking::Gorse::new(serve(2), "");
+        let options = EvaluationOptions {
+            k: 2,
+            ..EvaluationOptions::default()
+        };
+        let report = client.evaluate(&train, &test, &options)?;
+        assert_eq!(report.users, 2);
+        assert_close(report.precision, 0.5);
+        Ok(())
+    }
+}
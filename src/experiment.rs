@@ -0,0 +1,434 @@
+//! A/B testing of recommender configurations: users are assigned to
+//! weighted variants by a salted hash of their id, recommendations are
+//! requested with the client and options of their variant, and feedback
+//! written back is tagged with the variant.
+//!
+//! ```no_run
+//! # async fn run(baseline: gorse_rs::Gorse, candidate: gorse_rs::Gorse) -> Result<(), gorse_rs::Error> {
+//! use gorse_rs::experiment::Experiment;
+//! use gorse_rs::{Feedback, RecommendOptions};
+//!
+//! let experiment = Experiment::new("ranking")
+//!     .variant("control", 90, baseline, RecommendOptions { n: 10 })
+//!     .variant("candidate", 10, candidate, RecommendOptions { n: 10 });
+//! let (variant, scores) = experiment.get_recommend("bob").await?;
+//! println!("{} recommends {:?}", variant, scores);
+//! // Stored with the comment `ranking:control` or `ranking:candidate`.
+//! experiment
+//!     .insert_feedback(&[Feedback {
+//!         feedback_type: "click".into(),
+//!         user_id: "bob".into(),
+//!         item_id: scores[0].id.clone(),
+//!         value: 1.0,
+//!         timestamp: "2022-02-24T00:00:00Z".into(),
+//!     }])
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::Serialize;
+
+use crate::{Error, Feedback, Gorse, RecommendOptions, Result, RowAffected, Score};
+
+/// Experiment assigning users to variants, with clients of type `C`, e.g.
+/// [`Gorse`] or [`blocking::Gorse`](crate::blocking::Gorse).
+#[derive(Debug, Clone)]
+pub struct Experiment<C> {
+    name: String,
+    salt: String,
+    variants: Vec<Variant<C>>,
+}
+
+/// Variant of an experiment, served by a client with recommendation
+/// options.
+#[derive(Debug, Clone)]
+pub struct Variant<C> {
+    pub name: String,
+    /// Weight of the variant among the variants of the experiment.
+    pub weight: u32,
+    pub client: C,
+    pub options: RecommendOptions,
+}
+
+impl<C> Experiment<C> {
+    /// Creates an experiment without variants. The name salts the hash of
+    /// user ids, so that experiments assign users independently.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            salt: name.clone(),
+            name,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Replaces the salt of the hash of user ids, by default the name, e.g.
+    /// to reassign users while keeping the name.
+    pub fn salt(mut self, salt: impl Into<String>) -> Self {
+        self.salt = salt.into();
+        self
+    }
+
+    /// Adds a variant, assigned to `weight` out of the total weight of the
+    /// variants of users.
+    pub fn variant(
+        mut self,
+        name: impl Into<String>,
+        weight: u32,
+        client: C,
+        options: RecommendOptions,
+    ) -> Self {
+        self.variants.push(Variant {
+            name: name.into(),
+            weight,
+            client,
+            options,
+        });
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn variants(&self) -> &[Variant<C>] {
+        &self.variants
+    }
+
+    /// Variant of a user, the same for the same salt and variants. Returns
+    /// `None` if the variants have no weight.
+    pub fn assign(&self, user_id: &str) -> Option<&Variant<C>> {
+        let total: u64 = self
+            .variants
+            .iter()
+            .map(|variant| u64::from(variant.weight))
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut bucket = fnv1a(&[self.salt.as_bytes(), b":", user_id.as_bytes()]) % total;
+        self.variants.iter().find(|variant| {
+            let found = bucket < u64::from(variant.weight);
+            bucket = bucket.saturating_sub(u64::from(variant.weight));
+            found
+        })
+    }
+
+    /// Comment tagging the feedback of a user with the experiment and the
+    /// variant, e.g. `ranking:control`.
+    pub fn tag(&self, user_id: &str) -> Option<String> {
+        self.assign(user_id)
+            .map(|variant| format!("{}:{}", self.name, variant.name))
+    }
+
+    fn assigned(&self, user_id: &str) -> Result<&Variant<C>> {
+        self.assign(user_id).ok_or_else(|| {
+            Error::Config(format!("experiment {} has no weighted variants", self.name))
+        })
+    }
+
+    /// Feedback tagged with its variant, grouped by variant in the order of
+    /// the variants.
+    fn tag_feedback<'a>(
+        &'a self,
+        feedback: &'a [Feedback],
+    ) -> Result<Vec<(&'a Variant<C>, Vec<TaggedFeedback<'a>>)>> {
+        let mut groups: Vec<Vec<TaggedFeedback>> =
+            self.variants.iter().map(|_| Vec::new()).collect();
+        for feedback in feedback {
+            let variant = self.assigned(&feedback.user_id)?;
+            let index = self
+                .variants
+                .iter()
+                .position(|candidate| std::ptr::eq(candidate, variant))
+                .expect("assigned variants are variants of the experiment");
+            groups[index].push(TaggedFeedback {
+                feedback,
+                comment: format!("{}:{}", self.name, variant.name),
+            });
+        }
+        Ok(self
+            .variants
+            .iter()
+            .zip(groups)
+            .filter(|(_, group)| !group.is_empty())
+            .collect())
+    }
+}
+
+/// Failed insert of the feedback of an experiment. The feedback of each
+/// variant is inserted in turn, in the order of the variants, so that the
+/// feedback of the variants listed here stays inserted, and is inserted
+/// again by retrying the whole feedback. The feedback of the failed variant
+/// may be partly inserted if it is split into batches.
+#[derive(Debug, thiserror::Error)]
+#[error("inserting feedback failed after {} variants: {source}", variants.len())]
+pub struct InsertError {
+    /// Rows inserted through the variants whose feedback was inserted.
+    pub inserted: RowAffected,
+    /// Names of the variants whose feedback was inserted.
+    pub variants: Vec<String>,
+    pub source: Error,
+}
+
+impl InsertError {
+    fn new(source: Error) -> Self {
+        Self {
+            inserted: RowAffected { row_affected: 0 },
+            variants: Vec::new(),
+            source,
+        }
+    }
+}
+
+impl From<InsertError> for Error {
+    fn from(err: InsertError) -> Self {
+        err.source
+    }
+}
+
+/// Feedback with the comment of its variant, which the server stores with
+/// the feedback.
+#[derive(Serialize)]
+struct TaggedFeedback<'a> {
+    #[serde(flatten)]
+    feedback: &'a Feedback,
+    #[serde(rename = "Comment")]
+    comment: String,
+}
+
+/// 64-bit FNV-1a hash of concatenated bytes.
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+impl Experiment<Gorse> {
+    /// Get recommendation with scores for a user from the client and with
+    /// the options of their variant. Returns the name of the variant with
+    /// the recommendation.
+    pub async fn get_recommend(&self, user_id: &str) -> Result<(&str, Vec<Score>)> {
+        let variant = self.assigned(user_id)?;
+        let scores = variant
+            .client
+            .get_recommend(user_id, variant.options.clone())
+            .await?;
+        Ok((&variant.name, scores))
+    }
+
+    /// Inserts feedback through the clients of the variants of their users,
+    /// tagged with the experiment and variant in their comment, see
+    /// [`tag`](Self::tag). The insert is not atomic, see [`InsertError`].
+    pub async fn insert_feedback(
+        &self,
+        feedback: &[Feedback],
+    ) -> std::result::Result<RowAffected, InsertError> {
+        let mut inserted = RowAffected { row_affected: 0 };
+        let mut variants = Vec::new();
+        for (variant, tagged) in self.tag_feedback(feedback).map_err(InsertError::new)? {
+            match variant
+                .client
+                .insert_batches("insert_feedback", "api/feedback", &tagged)
+                .await
+            {
+                Ok(rows) => {
+                    inserted.row_affected += rows.row_affected;
+                    variants.push(variant.name.clone());
+                }
+                Err(source) => {
+                    return Err(InsertError {
+                        inserted,
+                        variants,
+                        source,
+                    })
+                }
+            }
+        }
+        Ok(inserted)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl Experiment<crate::blocking::Gorse> {
+    /// Get recommendation with scores for a user from the client and with
+    /// the options of their variant. Returns the name of the variant with
+    /// the recommendation.
+    pub fn get_recommend(&self, user_id: &str) -> Result<(&str, Vec<Score>)> {
+        let variant = self.assigned(user_id)?;
+        let scores = variant
+            .client
+            .get_recommend(user_id, variant.options.clone())?;
+        Ok((&variant.name, scores))
+    }
+
+    /// Inserts feedback through the clients of the variants of their users,
+    /// tagged with the experiment and variant in their comment, see
+    /// [`tag`](Self::tag). The insert is not atomic, see [`InsertError`].
+    pub fn insert_feedback(
+        &self,
+        feedback: &[Feedback],
+    ) -> std::result::Result<RowAffected, InsertError> {
+        let mut inserted = RowAffected { row_affected: 0 };
+        let mut variants = Vec::new();
+        for (variant, tagged) in self.tag_feedback(feedback).map_err(InsertError::new)? {
+            match variant
+                .client
+                .insert_batches("insert_feedback", "api/feedback", &tagged)
+            {
+                Ok(rows) => {
+                    inserted.row_affected += rows.row_affected;
+                    variants.push(variant.name.clone());
+                }
+                Err(source) => {
+                    return Err(InsertError {
+                        inserted,
+                        variants,
+                        source,
+                    })
+                }
+            }
+        }
+        Ok(inserted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{self, serve};
+    use serde_json::json;
+
+    fn experiment(control: u32, candidate: u32) -> Experiment<()> {
+        Experiment::new("ranking")
+            .variant("control", control, (), RecommendOptions { n: 10 })
+            .variant("candidate", candidate, (), RecommendOptions { n: 5 })
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(&[]), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(&[b"a"]), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(&[b"foo", b"bar"]), fnv1a(&[b"foobar"]));
+    }
+
+    #[test]
+    fn test_assign() {
+        let ranking = experiment(90, 10);
+        let users: Vec<String> = (0..10000).map(|i| format!("user{}", i)).collect();
+        let candidates = users
+            .iter()
+            .filter(|user| ranking.assign(user).unwrap().name == "candidate")
+            .count();
+        assert!((800..1200).contains(&candidates), "{}", candidates);
+        // Assignments are deterministic, and change with the salt.
+        let rebuilt = experiment(90, 10);
+        let resalted = experiment(90, 10).salt("ranking-2");
+        assert!(users
+            .iter()
+            .all(|user| ranking.assign(user).unwrap().name == rebuilt.assign(user).unwrap().name));
+        assert!(users
+            .iter()
+            .any(|user| ranking.assign(user).unwrap().name != resalted.assign(user).unwrap().name));
+
+        assert_eq!(
+            experiment(0, 1).tag("bob").as_deref(),
+            Some("ranking:candidate")
+        );
+        assert!(experiment(0, 0).assign("bob").is_none());
+        assert!(Experiment::<()>::new("empty").assign("bob").is_none());
+    }
+
+    #[test]
+    fn test_tag_feedback() -> Result<()> {
+        let ranking = experiment(1, 1);
+        let feedback: Vec<Feedback> = (0..20)
+            .map(|i| Feedback {
+                feedback_type: "click".into(),
+                user_id: format!("user{}", i),
+                item_id: "1".into(),
+                value: 1.0,
+                timestamp: String::new(),
+            })
+            .collect();
+        let groups = ranking.tag_feedback(&feedback)?;
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups.iter().map(|(_, tagged)| tagged.len()).sum::<usize>(),
+            20
+        );
+        for (variant, tagged) in &groups {
+            for tagged in tagged {
+                assert_eq!(
+                    ranking.assign(&tagged.feedback.user_id).unwrap().name,
+                    variant.name
+                );
+            }
+        }
+        let (variant, tagged) = &groups[0];
+        assert_eq!(
+            serde_json::to_value(&tagged[0])?,
+            json!({
+                "FeedbackType": "click",
+                "UserId": tagged[0].feedback.user_id,
+                "ItemId": "1",
+                "Value": 1.0,
+                "Timestamp": "",
+                "Comment": format!("ranking:{}", variant.name),
+            })
+        );
+        assert!(matches!(
+            Experiment::<()>::new("empty").tag_feedback(&feedback),
+            Err(Error::Config(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_feedback_failure() -> Result<()> {
+        let (entry_point, control) = serve(1, |_, body| {
+            let feedback: Vec<serde_json::Value> = serde_json::from_slice(body).unwrap();
+            test_server::json(&json!({ "RowAffected": feedback.len() }).to_string())
+        });
+        let (failing, candidate) = serve(1, |_, _| {
+            test_server::response("500 Internal Server Error", "text/plain", "failed")
+        });
+        let ranking = Experiment::new("ranking")
+            .variant(
+                "control",
+                1,
+                Gorse::new(entry_point, ""),
+                RecommendOptions { n: 10 },
+            )
+            .variant(
+                "candidate",
+                1,
+                Gorse::new(failing, ""),
+                RecommendOptions { n: 10 },
+            );
+        let feedback: Vec<Feedback> = (0..20)
+            .map(|i| Feedback {
+                feedback_type: "click".into(),
+                user_id: format!("user{}", i),
+                item_id: "1".into(),
+                value: 1.0,
+                timestamp: String::new(),
+            })
+            .collect();
+        let controls = feedback
+            .iter()
+            .filter(|feedback| ranking.assign(&feedback.user_id).unwrap().name == "control")
+            .count();
+        let err = ranking.insert_feedback(&feedback).await.unwrap_err();
+        assert_eq!(err.inserted.row_affected as usize, controls);
+        assert_eq!(err.variants, vec!["control"]);
+        assert!(matches!(err.source, Error::Api { .. }));
+        control.join().unwrap();
+        candidate.join().unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+
+use crate::{Error, Result, Score};
+
+/// A source of recommendations in a fallback chain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fallback {
+    /// Personalized recommendations for the user.
+    Personalized,
+    /// Popular items in a category.
+    CategoryPopular(String),
+    /// Popular items across all categories.
+    Popular,
+    /// Latest items in a category.
+    CategoryLatest(String),
+    /// Latest items across all categories.
+    Latest,
+    /// A caller supplied list of item ids, e.g. editorial picks.
+    Static(Vec<String>),
+}
+
+impl Fallback {
+    pub fn source(&self) -> FallbackSource {
+        match self {
+            Fallback::Personalized => FallbackSource::Personalized,
+            Fallback::CategoryPopular(category) => {
+                FallbackSource::CategoryPopular(category.clone())
+            }
+            Fallback::Popular => FallbackSource::Popular,
+            Fallback::CategoryLatest(category) => FallbackSource::CategoryLatest(category.clone()),
+            Fallback::Latest => FallbackSource::Latest,
+            Fallback::Static(_) => FallbackSource::Static,
+        }
+    }
+}
+
+/// The [`Fallback`] that filled a slot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FallbackSource {
+    Personalized,
+    CategoryPopular(String),
+    Popular,
+    CategoryLatest(String),
+    Latest,
+    Static,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FallbackOptions {
+    /// Number of items to return.
+    pub n: usize,
+    /// Sources tried in order until `n` items are collected.
+    pub strategies: Vec<Fallback>,
+    /// Item ids never returned, e.g. items the user has already seen.
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FallbackScore {
+    pub id: String,
+    /// Score reported by the source, 0 for static items.
+    pub score: f64,
+    pub source: FallbackSource,
+}
+
+#[derive(Debug)]
+pub struct FallbackRecommendation {
+    pub items: Vec<FallbackScore>,
+    /// Errors of the sources that failed and were skipped.
+    pub errors: Vec<(FallbackSource, Error)>,
+}
+
+/// Merges the results of fallback sources, shared by both clients.
+pub(crate) struct FallbackCollector {
+    n: usize,
+    seen: HashSet<String>,
+    items: Vec<FallbackScore>,
+    errors: Vec<(FallbackSource, Error)>,
+}
+
+impl FallbackCollector {
+    pub(crate) fn new(options: &FallbackOptions) -> Self {
+        Self {
+            n: options.n,
+            seen: options.exclude.iter().cloned().collect(),
+            items: Vec::with_capacity(options.n),
+            errors: Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.items.len() >= self.n
+    }
+
+    /// Number of items to request from the next source, leaving room for
+    /// items dropped as duplicates or exclusions.
+    pub(crate) fn fetch_size(&self) -> usize {
+        self.n - self.items.len() + self.seen.len()
+    }
+
+    pub(crate) fn collect(&mut self, strategy: &Fallback, result: Result<Vec<Score>>) {
+        let source = strategy.source();
+        let scores = match (strategy, result) {
+            (Fallback::Static(ids), _) => ids
+                .iter()
+                .map(|id| Score {
+                    id: id.clone(),
+                    score: 0.0,
+                })
+                .collect(),
+            (_, Ok(scores)) => scores,
+            (_, Err(err)) => {
+                self.errors.push((source, err));
+                return;
+            }
+        };
+        for score in scores {
+            if self.is_full() {
+                break;
+            }
+            if self.seen.insert(score.id.clone()) {
+                self.items.push(FallbackScore {
+                    id: score.id,
+                    score: score.score,
+                    source: source.clone(),
+                });
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> FallbackRecommendation {
+        FallbackRecommendation {
+            items: self.items,
+            errors: self.errors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(ids: &[&str]) -> Vec<Score> {
+        ids.iter()
+            .map(|id| Score {
+                id: id.to_string(),
+                score: 1.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_collect() {
+        let options = FallbackOptions {
+            n: 4,
+            strategies: vec![
+                Fallback::Personalized,
+                Fallback::Popular,
+                Fallback::Static(vec!["a".into(), "e".into(), "f".into()]),
+            ],
+            exclude: vec!["b".into()],
+        };
+        let mut collector = FallbackCollector::new(&options);
+        assert_eq!(collector.fetch_size(), 5);
+        collector.collect(
+            &options.strategies[0],
+            Err(Error::Config("unavailable".into())),
+        );
+        collector.collect(&options.strategies[1], Ok(scores(&["a", "b", "c"])));
+        assert!(!collector.is_full());
+        collector.collect(&options.strategies[2], Ok(Vec::new()));
+        assert!(collector.is_full());
+
+        let recommendation = collector.finish();
+        let ids: Vec<_> = recommendation.items.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "c", "e", "f"]);
+        assert_eq!(recommendation.items[1].source, FallbackSource::Popular);
+        assert_eq!(recommendation.items[2].source, FallbackSource::Static);
+        assert_eq!(recommendation.errors.len(), 1);
+        assert_eq!(recommendation.errors[0].0, FallbackSource::Personalized);
+    }
+}
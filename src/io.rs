@@ -0,0 +1,1129 @@
+//! Reading and writing users, items and feedback as CSV or JSON Lines, and
+//! importing and exporting them through the batch insert and listing APIs.
+//!
+//! ```no_run
+//! # async fn run(client: gorse_rs::Gorse) -> Result<(), gorse_rs::Error> {
+//! use gorse_rs::io::{CsvOptions, Format};
+//! use gorse_rs::Feedback;
+//!
+//! let file = tokio::fs::File::open("feedback.csv").await?;
+//! let format = Format::Csv(CsvOptions::default());
+//! let report = client
+//!     .import::<Feedback>(file, &format, |report| println!("{} rows", report.rows))
+//!     .await?;
+//! for error in &report.errors {
+//!     eprintln!("row {}: {}", error.row, error.message);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use csv_core::{ReadRecordResult, WriteResult};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Error, Feedback, Gorse, Item, PageStream, Result, RowAffected, User};
+
+/// Records per request of an import or export when the client has no
+/// [`batch_size`](crate::GorseBuilder::batch_size).
+const DEFAULT_BATCH_SIZE: usize = 1000;
+/// Size of the chunks read from readers, and of the data buffered before
+/// writing to writers.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Format of a file of records.
+#[derive(Debug, Clone)]
+pub enum Format {
+    /// Delimiter-separated values, one record per row.
+    Csv(CsvOptions),
+    /// One JSON object per line, with the fields of the API, e.g.
+    /// `{"UserId":"bob","Labels":null,"Comment":""}`.
+    JsonLines,
+}
+
+/// Options of [`Format::Csv`]. The columns of each record type are listed
+/// by [`Record`].
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Field delimiter, `,` by default.
+    pub delimiter: u8,
+    /// Whether the first row names the columns, `true` by default. Without
+    /// a header, columns are in the order listed by [`Record`].
+    pub header: bool,
+    /// Separator of the categories of an item, `|` by default.
+    pub separator: char,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            header: true,
+            separator: '|',
+        }
+    }
+}
+
+/// Record read and written by this module: [`User`], [`Item`] or
+/// [`Feedback`].
+///
+/// The CSV columns are:
+/// - users: `user_id`, `labels`, `comment`;
+/// - items: `item_id`, `is_hidden`, `categories`, `timestamp`, `labels`,
+///   `comment`;
+/// - feedback: `feedback_type`, `user_id`, `item_id`, `value`, `timestamp`.
+///
+/// Labels are JSON and categories are joined by [`CsvOptions::separator`].
+/// Header names ignore case and underscores, e.g. `UserId`, and may be in
+/// any order. The type and ids are required, other columns may be empty or
+/// missing.
+pub trait Record: Serialize + DeserializeOwned + private::Sealed {}
+
+impl Record for User {}
+impl Record for Item {}
+impl Record for Feedback {}
+
+mod private {
+    pub trait Sealed: Sized {
+        const COLUMNS: &'static [&'static str];
+        /// Number of leading columns that are required.
+        const REQUIRED: usize;
+        const PATH: &'static str;
+        const INSERT_ENDPOINT: &'static str;
+        const LIST_ENDPOINT: &'static str;
+
+        fn from_row(row: &Row<'_>, separator: char) -> std::result::Result<Self, String>;
+
+        fn to_row(&self, separator: char) -> Vec<String>;
+    }
+
+    /// Fields of a CSV row, by column of the record type.
+    pub struct Row<'a> {
+        pub(super) names: &'static [&'static str],
+        pub(super) fields: &'a [String],
+        /// Position of each column in the row, if present.
+        pub(super) positions: &'a [Option<usize>],
+    }
+
+    impl Row<'_> {
+        pub fn get(&self, column: usize) -> &str {
+            self.positions[column]
+                .and_then(|position| self.fields.get(position))
+                .map_or("", String::as_str)
+        }
+
+        pub fn required(&self, column: usize) -> std::result::Result<String, String> {
+            match self.get(column) {
+                "" => Err(format!("missing {}", self.names[column])),
+                field => Ok(field.to_string()),
+            }
+        }
+    }
+}
+
+use private::{Row, Sealed};
+
+impl Sealed for User {
+    const COLUMNS: &'static [&'static str] = &["user_id", "labels", "comment"];
+    const REQUIRED: usize = 1;
+    const PATH: &'static str = "api/users";
+    const INSERT_ENDPOINT: &'static str = "insert_users";
+    const LIST_ENDPOINT: &'static str = "get_users";
+
+    fn from_row(row: &Row<'_>, _separator: char) -> std::result::Result<Self, String> {
+        Ok(User {
+            user_id: row.required(0)?,
+            labels: parse_labels(row.get(1))?,
+            comment: row.get(2).to_string(),
+        })
+    }
+
+    fn to_row(&self, _separator: char) -> Vec<String> {
+        vec![
+            self.user_id.clone(),
+            format_labels(&self.labels),
+            self.comment.clone(),
+        ]
+    }
+}
+
+impl Sealed for Item {
+    const COLUMNS: &'static [&'static str] = &[
+        "item_id",
+        "is_hidden",
+        "categories",
+        "timestamp",
+        "labels",
+        "comment",
+    ];
+    const REQUIRED: usize = 1;
+    const PATH: &'static str = "api/items";
+    const INSERT_ENDPOINT: &'static str = "insert_items";
+    const LIST_ENDPOINT: &'static str = "get_items";
+
+    fn from_row(row: &Row<'_>, separator: char) -> std::result::Result<Self, String> {
+        let is_hidden = match row.get(1).to_ascii_lowercase().as_str() {
+            "" | "false" | "0" => false,
+            "true" | "1" => true,
+            field => return Err(format!("invalid is_hidden: {:?}", field)),
+        };
+        let categories = match row.get(2) {
+            "" => Vec::new(),
+            field => field.split(separator).map(str::to_string).collect(),
+        };
+        Ok(Item {
+            item_id: row.required(0)?,
+            is_hidden,
+            categories,
+            timestamp: row.get(3).to_string(),
+            labels: parse_labels(row.get(4))?,
+            comment: row.get(5).to_string(),
+        })
+    }
+
+    fn to_row(&self, separator: char) -> Vec<String> {
+        vec![
+            self.item_id.clone(),
+            self.is_hidden.to_string(),
+            self.categories.join(separator.encode_utf8(&mut [0; 4])),
+            self.timestamp.clone(),
+            format_labels(&self.labels),
+            self.comment.clone(),
+        ]
+    }
+}
+
+impl Sealed for Feedback {
+    const COLUMNS: &'static [&'static str] =
+        &["feedback_type", "user_id", "item_id", "value", "timestamp"];
+    const REQUIRED: usize = 3;
+    const PATH: &'static str = "api/feedback";
+    const INSERT_ENDPOINT: &'static str = "insert_feedback";
+    const LIST_ENDPOINT: &'static str = "get_feedback";
+
+    fn from_row(row: &Row<'_>, _separator: char) -> std::result::Result<Self, String> {
+        let value = match row.get(3) {
+            "" => 0.0,
+            field => field
+                .parse()
+                .map_err(|_| format!("invalid value: {:?}", field))?,
+        };
+        Ok(Feedback {
+            feedback_type: row.required(0)?,
+            user_id: row.required(1)?,
+            item_id: row.required(2)?,
+            value,
+            timestamp: row.get(4).to_string(),
+        })
+    }
+
+    fn to_row(&self, _separator: char) -> Vec<String> {
+        vec![
+            self.feedback_type.clone(),
+            self.user_id.clone(),
+            self.item_id.clone(),
+            self.value.to_string(),
+            self.timestamp.clone(),
+        ]
+    }
+}
+
+fn parse_labels(field: &str) -> std::result::Result<Value, String> {
+    if field.is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(field).map_err(|err| format!("invalid labels: {}", err))
+}
+
+fn format_labels(labels: &Value) -> String {
+    match labels {
+        Value::Null => String::new(),
+        labels => labels.to_string(),
+    }
+}
+
+/// Invalid record skipped by an import.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    /// Line of a JSON Lines file, or record of a CSV file not counting the
+    /// header, starting at 1.
+    pub row: u64,
+    pub message: String,
+}
+
+/// Progress and result of [`Gorse::import`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ImportReport {
+    /// Records read, valid or not.
+    pub rows: u64,
+    /// Rows affected by the inserts.
+    pub inserted: u64,
+    /// Invalid records, which were skipped.
+    pub errors: Vec<RowError>,
+}
+
+/// Failed import, with the report of the rows read and inserted until the
+/// failure. The batches counted as inserted stay inserted.
+#[derive(Debug, thiserror::Error)]
+#[error("import failed after {} rows, {} inserted: {source}", report.rows, report.inserted)]
+pub struct ImportError {
+    pub report: ImportReport,
+    pub source: Error,
+}
+
+impl From<ImportError> for Error {
+    fn from(err: ImportError) -> Self {
+        err.source
+    }
+}
+
+/// Batches and report of an import, shared by the clients, which read the
+/// records and insert the batches.
+#[derive(Debug)]
+struct Importer<T> {
+    batch_size: usize,
+    batch: Vec<T>,
+    report: ImportReport,
+}
+
+impl<T> Importer<T> {
+    fn new(batch_size: Option<usize>) -> Self {
+        let batch_size = batch_size.unwrap_or(DEFAULT_BATCH_SIZE).max(1);
+        Self {
+            batch_size,
+            batch: Vec::with_capacity(batch_size),
+            report: ImportReport::default(),
+        }
+    }
+
+    /// Adds a record read, or `None` at the end of the file. Returns
+    /// whether the batch is due, i.e. full, or not empty at the end.
+    fn read(&mut self, record: Option<Result<T>>) -> Result<bool> {
+        let Some(record) = record else {
+            return Ok(!self.batch.is_empty());
+        };
+        match record {
+            Ok(record) => self.batch.push(record),
+            Err(Error::InvalidRecord { row, message }) => {
+                self.report.errors.push(RowError { row, message })
+            }
+            Err(err) => return Err(err),
+        }
+        self.report.rows += 1;
+        Ok(self.batch.len() >= self.batch_size)
+    }
+
+    /// Counts the rows inserted from the batch, which starts over, and
+    /// returns the report.
+    fn inserted(&mut self, inserted: RowAffected) -> &ImportReport {
+        self.report.inserted += u64::try_from(inserted.row_affected).unwrap_or(0);
+        self.batch.clear();
+        &self.report
+    }
+
+    fn fail(self, source: Error) -> ImportError {
+        ImportError {
+            report: self.report,
+            source,
+        }
+    }
+}
+
+/// Splits chunks of a file into records.
+#[derive(Debug)]
+enum Decoder {
+    Csv(Box<CsvDecoder>),
+    Lines(LineDecoder),
+}
+
+impl Decoder {
+    fn new(format: &Format) -> Self {
+        match format {
+            Format::Csv(options) => Decoder::Csv(Box::new(CsvDecoder::new(options))),
+            Format::JsonLines => Decoder::Lines(LineDecoder::default()),
+        }
+    }
+
+    /// Feeds a chunk of the file, or an empty chunk at the end, and appends
+    /// the completed records. Errors of a single record are appended as
+    /// [`Error::InvalidRecord`], other errors are returned.
+    fn decode<T: Record>(&mut self, chunk: &[u8], records: &mut VecDeque<Result<T>>) -> Result<()> {
+        match self {
+            Decoder::Csv(decoder) => decoder.decode(chunk, records),
+            Decoder::Lines(decoder) => {
+                decoder.decode(chunk, records);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CsvDecoder {
+    reader: csv_core::Reader,
+    header: bool,
+    separator: char,
+    /// Position of each column of the record type, known after the header.
+    positions: Option<Vec<Option<usize>>>,
+    output: Vec<u8>,
+    output_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+    row: u64,
+}
+
+impl CsvDecoder {
+    fn new(options: &CsvOptions) -> Self {
+        Self {
+            reader: csv_core::ReaderBuilder::new()
+                .delimiter(options.delimiter)
+                .build(),
+            header: options.header,
+            separator: options.separator,
+            positions: None,
+            output: vec![0; 1024],
+            output_len: 0,
+            ends: vec![0; 16],
+            ends_len: 0,
+            row: 0,
+        }
+    }
+
+    fn decode<T: Record>(
+        &mut self,
+        mut chunk: &[u8],
+        records: &mut VecDeque<Result<T>>,
+    ) -> Result<()> {
+        let end = chunk.is_empty();
+        // The reader takes an empty input for the end of the file.
+        while end || !chunk.is_empty() {
+            let (result, nin, nout, nend) = self.reader.read_record(
+                chunk,
+                &mut self.output[self.output_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            chunk = &chunk[nin..];
+            self.output_len += nout;
+            self.ends_len += nend;
+            match result {
+                ReadRecordResult::InputEmpty | ReadRecordResult::End => break,
+                ReadRecordResult::OutputFull => self.output.resize(self.output.len() * 2, 0),
+                ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                ReadRecordResult::Record => {
+                    self.record(records)?;
+                    self.output_len = 0;
+                    self.ends_len = 0;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn record<T: Record>(&mut self, records: &mut VecDeque<Result<T>>) -> Result<()> {
+        let mut start = 0;
+        let fields: std::result::Result<Vec<String>, _> = self.ends[..self.ends_len]
+            .iter()
+            .map(|&end| {
+                let field = std::str::from_utf8(&self.output[start..end]).map(str::to_string);
+                start = end;
+                field
+            })
+            .collect();
+        let positions = match &self.positions {
+            Some(positions) => positions,
+            None if self.header => {
+                let header = fields.map_err(|_| invalid_data("invalid UTF-8 in the CSV header"))?;
+                self.positions = Some(header_positions::<T>(&header)?);
+                return Ok(());
+            }
+            None => self
+                .positions
+                .insert((0..T::COLUMNS.len()).map(Some).collect()),
+        };
+        self.row += 1;
+        let record = fields
+            .map_err(|_| "invalid UTF-8".to_string())
+            .and_then(|fields| {
+                let row = Row {
+                    names: T::COLUMNS,
+                    fields: &fields,
+                    positions,
+                };
+                T::from_row(&row, self.separator)
+            })
+            .map_err(|message| Error::InvalidRecord {
+                row: self.row,
+                message,
+            });
+        records.push_back(record);
+        Ok(())
+    }
+}
+
+/// Positions of the columns of a record type in a CSV header.
+fn header_positions<T: Record>(header: &[String]) -> Result<Vec<Option<usize>>> {
+    let normalize = |name: &str| name.replace('_', "").trim().to_ascii_lowercase();
+    let header: Vec<String> = header.iter().map(|name| normalize(name)).collect();
+    T::COLUMNS
+        .iter()
+        .enumerate()
+        .map(|(column, name)| {
+            let position = header.iter().position(|field| *field == normalize(name));
+            if position.is_none() && column < T::REQUIRED {
+                return Err(invalid_data(&format!(
+                    "missing {} column in the CSV header",
+                    name
+                )));
+            }
+            Ok(position)
+        })
+        .collect()
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+#[derive(Debug, Default)]
+struct LineDecoder {
+    line: Vec<u8>,
+    row: u64,
+}
+
+impl LineDecoder {
+    fn decode<T: Record>(&mut self, mut chunk: &[u8], records: &mut VecDeque<Result<T>>) {
+        let end = chunk.is_empty();
+        while let Some(position) = chunk.iter().position(|&byte| byte == b'\n') {
+            self.line.extend_from_slice(&chunk[..position]);
+            chunk = &chunk[position + 1..];
+            self.finish_line(records);
+        }
+        self.line.extend_from_slice(chunk);
+        if end && !self.line.is_empty() {
+            self.finish_line(records);
+        }
+    }
+
+    fn finish_line<T: Record>(&mut self, records: &mut VecDeque<Result<T>>) {
+        self.row += 1;
+        if !self.line.iter().all(u8::is_ascii_whitespace) {
+            let record = serde_json::from_slice(&self.line).map_err(|err| Error::InvalidRecord {
+                row: self.row,
+                message: err.to_string(),
+            });
+            records.push_back(record);
+        }
+        self.line.clear();
+    }
+}
+
+/// Writes records in a format.
+#[derive(Debug)]
+enum Encoder {
+    Csv {
+        writer: Box<csv_core::Writer>,
+        separator: char,
+    },
+    Lines,
+}
+
+impl Encoder {
+    /// Creates an encoder and writes the header of the file to `output`.
+    fn new<T: Record>(format: &Format, output: &mut Vec<u8>) -> Self {
+        match format {
+            Format::Csv(options) => {
+                let mut writer = csv_core::WriterBuilder::new()
+                    .delimiter(options.delimiter)
+                    .build();
+                if options.header {
+                    write_csv_row(&mut writer, T::COLUMNS, output);
+                }
+                Encoder::Csv {
+                    writer: Box::new(writer),
+                    separator: options.separator,
+                }
+            }
+            Format::JsonLines => Encoder::Lines,
+        }
+    }
+
+    fn encode<T: Record>(&mut self, record: &T, output: &mut Vec<u8>) -> Result<()> {
+        match self {
+            Encoder::Csv { writer, separator } => {
+                write_csv_row(writer, &record.to_row(*separator), output)
+            }
+            Encoder::Lines => {
+                serde_json::to_writer(&mut *output, record)?;
+                output.push(b'\n');
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_csv_row(writer: &mut csv_core::Writer, fields: &[impl AsRef<str>], output: &mut Vec<u8>) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            write_csv(output, 2, |buffer| writer.delimiter(buffer));
+        }
+        let mut field = field.as_ref().as_bytes();
+        loop {
+            let mut nin = 0;
+            // Quoting at most doubles the field and adds two quotes.
+            let result = write_csv(output, field.len() * 2 + 2, |buffer| {
+                let (result, read, written) = writer.field(field, buffer);
+                nin = read;
+                (result, written)
+            });
+            field = &field[nin..];
+            if let WriteResult::InputEmpty = result {
+                break;
+            }
+        }
+    }
+    write_csv(output, 4, |buffer| writer.terminator(buffer));
+}
+
+fn write_csv(
+    output: &mut Vec<u8>,
+    len: usize,
+    write: impl FnOnce(&mut [u8]) -> (WriteResult, usize),
+) -> WriteResult {
+    let start = output.len();
+    output.resize(start + len, 0);
+    let (result, written) = write(&mut output[start..]);
+    output.truncate(start + written);
+    result
+}
+
+/// Records read from a file, e.g. of feedback:
+///
+/// ```no_run
+/// # fn run() -> Result<(), gorse_rs::Error> {
+/// use gorse_rs::io::{CsvOptions, Format, RecordReader};
+/// use gorse_rs::Feedback;
+///
+/// let file = std::fs::File::open("feedback.csv")?;
+/// for feedback in RecordReader::<_, Feedback>::new(file, &Format::Csv(CsvOptions::default())) {
+///     println!("{:?}", feedback?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// An invalid record is an [`Error::InvalidRecord`], after which reading
+/// continues. Reading stops after other errors.
+#[derive(Debug)]
+pub struct RecordReader<R, T> {
+    inner: R,
+    decoder: Decoder,
+    records: VecDeque<Result<T>>,
+    buffer: Box<[u8]>,
+    finished: bool,
+}
+
+impl<R: std::io::Read, T: Record> RecordReader<R, T> {
+    pub fn new(inner: R, format: &Format) -> Self {
+        Self {
+            inner,
+            decoder: Decoder::new(format),
+            records: VecDeque::new(),
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            finished: false,
+        }
+    }
+
+    fn read_chunk(&mut self) -> Result<()> {
+        let n = loop {
+            match self.inner.read(&mut self.buffer) {
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                n => break n?,
+            }
+        };
+        self.finished = n == 0;
+        self.decoder.decode(&self.buffer[..n], &mut self.records)
+    }
+}
+
+impl<R: std::io::Read, T: Record> Iterator for RecordReader<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Some(record);
+            }
+            if self.finished {
+                return None;
+            }
+            if let Err(err) = self.read_chunk() {
+                self.finished = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// Records read from an asynchronous file, see [`RecordReader`].
+#[derive(Debug)]
+pub struct AsyncRecordReader<R, T> {
+    inner: R,
+    decoder: Decoder,
+    records: VecDeque<Result<T>>,
+    buffer: Box<[u8]>,
+    finished: bool,
+}
+
+impl<R: AsyncRead + Unpin, T: Record> AsyncRecordReader<R, T> {
+    pub fn new(inner: R, format: &Format) -> Self {
+        Self {
+            inner,
+            decoder: Decoder::new(format),
+            records: VecDeque::new(),
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            finished: false,
+        }
+    }
+
+    /// Returns the next record, or `None` at the end of the file. An
+    /// invalid record is an [`Error::InvalidRecord`], after which reading
+    /// continues.
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Some(record);
+            }
+            if self.finished {
+                return None;
+            }
+            if let Err(err) = self.read_chunk().await {
+                self.finished = true;
+                return Some(Err(err));
+            }
+        }
+    }
+
+    async fn read_chunk(&mut self) -> Result<()> {
+        let n = self.inner.read(&mut self.buffer).await?;
+        self.finished = n == 0;
+        self.decoder.decode(&self.buffer[..n], &mut self.records)
+    }
+}
+
+/// Writes records to a file, buffered until [`finish`](Self::finish).
+#[derive(Debug)]
+pub struct RecordWriter<W, T> {
+    inner: W,
+    encoder: Encoder,
+    buffer: Vec<u8>,
+    record: PhantomData<fn(&T)>,
+}
+
+impl<W: std::io::Write, T: Record> RecordWriter<W, T> {
+    pub fn new(inner: W, format: &Format) -> Self {
+        let mut buffer = Vec::new();
+        let encoder = Encoder::new::<T>(format, &mut buffer);
+        Self {
+            inner,
+            encoder,
+            buffer,
+            record: PhantomData,
+        }
+    }
+
+    pub fn write(&mut self, record: &T) -> Result<()> {
+        self.encoder.encode(record, &mut self.buffer)?;
+        if self.buffer.len() >= BUFFER_SIZE {
+            self.inner.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered records, flushes and returns the writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.write_all(&self.buffer)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Writes records to an asynchronous file, see [`RecordWriter`].
+#[derive(Debug)]
+pub struct AsyncRecordWriter<W, T> {
+    inner: W,
+    encoder: Encoder,
+    buffer: Vec<u8>,
+    record: PhantomData<fn(&T)>,
+}
+
+impl<W: AsyncWrite + Unpin, T: Record> AsyncRecordWriter<W, T> {
+    pub fn new(inner: W, format: &Format) -> Self {
+        let mut buffer = Vec::new();
+        let encoder = Encoder::new::<T>(format, &mut buffer);
+        Self {
+            inner,
+            encoder,
+            buffer,
+            record: PhantomData,
+        }
+    }
+
+    pub async fn write(&mut self, record: &T) -> Result<()> {
+        self.encoder.encode(record, &mut self.buffer)?;
+        if self.buffer.len() >= BUFFER_SIZE {
+            self.inner.write_all(&self.buffer).await?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered records, flushes and returns the writer.
+    pub async fn finish(mut self) -> Result<W> {
+        self.inner.write_all(&self.buffer).await?;
+        self.inner.flush().await?;
+        Ok(self.inner)
+    }
+}
+
+impl Gorse {
+    /// Imports the records of a file, inserted in batches of the configured
+    /// [`batch_size`](crate::GorseBuilder::batch_size), or of 1000 records.
+    /// Invalid records are skipped and reported. `progress` is called after
+    /// each batch. Batches sent before a failure stay inserted, and are
+    /// counted by the report of the [`ImportError`].
+    pub async fn import<T: Record>(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        format: &Format,
+        mut progress: impl FnMut(&ImportReport),
+    ) -> std::result::Result<ImportReport, ImportError> {
+        let mut importer = Importer::new(self.batch_size);
+        let mut reader = AsyncRecordReader::<_, T>::new(reader, format);
+        loop {
+            let record = reader.next().await;
+            let finished = record.is_none();
+            match importer.read(record) {
+                Ok(false) => {}
+                Ok(true) => {
+                    match self
+                        .insert_batches(T::INSERT_ENDPOINT, T::PATH, &importer.batch)
+                        .await
+                    {
+                        Ok(inserted) => progress(importer.inserted(inserted)),
+                        Err(err) => return Err(importer.fail(err)),
+                    }
+                }
+                Err(err) => return Err(importer.fail(err)),
+            }
+            if finished {
+                return Ok(importer.report);
+            }
+        }
+    }
+
+    /// Exports all records of a type to a file, listed by pages of the
+    /// configured [`batch_size`](crate::GorseBuilder::batch_size), or of 1000
+    /// records, and written as they arrive. `progress` is called with the
+    /// number of records written after each page worth of records and at the
+    /// end. Returns the number of records written.
+    pub async fn export<T: Record>(
+        &self,
+        writer: impl AsyncWrite + Unpin,
+        format: &Format,
+        mut progress: impl FnMut(u64),
+    ) -> Result<u64> {
+        let n = self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE).max(1);
+        let mut writer = AsyncRecordWriter::<_, T>::new(writer, format);
+        let mut records = PageStream::<T>::new(self.clone(), T::LIST_ENDPOINT, T::PATH, n);
+        let mut rows = 0;
+        while let Some(record) = records.next().await? {
+            writer.write(&record).await?;
+            rows += 1;
+            if rows % n as u64 == 0 {
+                progress(rows);
+            }
+        }
+        writer.finish().await?;
+        progress(rows);
+        Ok(rows)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl crate::blocking::Gorse {
+    /// Imports the records of a file, inserted in batches of the configured
+    /// [`batch_size`](crate::GorseBuilder::batch_size), or of 1000 records.
+    /// Invalid records are skipped and reported. `progress` is called after
+    /// each batch. Batches sent before a failure stay inserted, and are
+    /// counted by the report of the [`ImportError`].
+    pub fn import<T: Record>(
+        &self,
+        reader: impl std::io::Read,
+        format: &Format,
+        mut progress: impl FnMut(&ImportReport),
+    ) -> std::result::Result<ImportReport, ImportError> {
+        let mut importer = Importer::new(self.batch_size);
+        let mut reader = RecordReader::<_, T>::new(reader, format);
+        loop {
+            let record = reader.next();
+            let finished = record.is_none();
+            match importer.read(record) {
+                Ok(false) => {}
+                Ok(true) => {
+                    match self.insert_batches(T::INSERT_ENDPOINT, T::PATH, &importer.batch) {
+                        Ok(inserted) => progress(importer.inserted(inserted)),
+                        Err(err) => return Err(importer.fail(err)),
+                    }
+                }
+                Err(err) => return Err(importer.fail(err)),
+            }
+            if finished {
+                return Ok(importer.report);
+            }
+        }
+    }
+
+    /// Exports all records of a type to a file, listed by pages of the
+    /// configured [`batch_size`](crate::GorseBuilder::batch_size), or of 1000
+    /// records, and written as they are read. `progress` is called with the
+    /// number of records written after each page worth of records and at the
+    /// end. Returns the number of records written.
+    pub fn export<T: Record>(
+        &self,
+        writer: impl std::io::Write,
+        format: &Format,
+        mut progress: impl FnMut(u64),
+    ) -> Result<u64> {
+        let n = self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE).max(1);
+        let mut writer = RecordWriter::<_, T>::new(writer, format);
+        let mut rows = 0;
+        for record in crate::PageIter::<T>::new(self.clone(), T::LIST_ENDPOINT, T::PATH, n) {
+            writer.write(&record?)?;
+            rows += 1;
+            if rows % n as u64 == 0 {
+                progress(rows);
+            }
+        }
+        writer.finish()?;
+        progress(rows);
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{self, serve};
+    use serde_json::json;
+
+    fn decode<T: Record>(format: &Format, chunks: &[&[u8]]) -> Result<Vec<Result<T>>> {
+        let mut decoder = Decoder::new(format);
+        let mut records = VecDeque::new();
+        for chunk in chunks.iter().copied().chain([&b""[..]]) {
+            decoder.decode(chunk, &mut records)?;
+        }
+        Ok(records.into())
+    }
+
+    fn encode<T: Record>(format: &Format, records: &[T]) -> Result<Vec<u8>> {
+        let mut writer = RecordWriter::new(Vec::new(), format);
+        for record in records {
+            writer.write(record)?;
+        }
+        writer.finish()
+    }
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item {
+                item_id: "1".into(),
+                is_hidden: true,
+                labels: json!({"genre": ["comedy", "drama"]}),
+                categories: vec!["a".into(), "b,c".into()],
+                timestamp: "2022-01-01T00:00:00Z".into(),
+                comment: "multi\nline \"comment\"".into(),
+            },
+            Item {
+                item_id: "2".into(),
+                is_hidden: false,
+                labels: Value::Null,
+                categories: Vec::new(),
+                timestamp: String::new(),
+                comment: String::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_csv() -> Result<()> {
+        let format = Format::Csv(CsvOptions::default());
+        let csv = encode(&format, &items())?;
+        assert!(csv.starts_with(b"item_id,is_hidden,categories,timestamp,labels,comment\n"));
+        // Split the file between every pair of bytes.
+        for i in 0..csv.len() {
+            let decoded = decode::<Item>(&format, &[&csv[..i], &csv[i..]])?;
+            let decoded: Vec<Item> = decoded.into_iter().collect::<Result<_>>()?;
+            assert_eq!(decoded, items());
+        }
+
+        let format = Format::Csv(CsvOptions {
+            delimiter: b';',
+            header: false,
+            separator: '/',
+        });
+        let csv = encode(&format, &items())?;
+        assert!(csv.starts_with(b"1;true;a/b,c;"));
+        let decoded: Vec<Item> = RecordReader::new(&csv[..], &format).collect::<Result<_>>()?;
+        assert_eq!(decoded, items());
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_header() -> Result<()> {
+        let format = Format::Csv(CsvOptions::default());
+        let csv = "ItemId,Timestamp,UserId,FeedbackType,Extra\n1,,bob,star,x\n";
+        let feedback = decode::<Feedback>(&format, &[csv.as_bytes()])?;
+        assert_eq!(
+            feedback.into_iter().collect::<Result<Vec<_>>>()?,
+            vec![Feedback {
+                feedback_type: "star".into(),
+                user_id: "bob".into(),
+                item_id: "1".into(),
+                value: 0.0,
+                timestamp: String::new(),
+            }]
+        );
+        assert!(matches!(
+            decode::<Feedback>(&format, &[b"user_id,item_id\nbob,1\n"]),
+            Err(Error::Io(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_lines() -> Result<()> {
+        let format = Format::JsonLines;
+        let lines = encode(&format, &items())?;
+        assert_eq!(lines.iter().filter(|&&byte| byte == b'\n').count(), 2);
+        for i in 0..lines.len() {
+            let decoded = decode::<Item>(&format, &[&lines[..i], &lines[i..]])?;
+            let decoded: Vec<Item> = decoded.into_iter().collect::<Result<_>>()?;
+            assert_eq!(decoded, items());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_records() -> Result<()> {
+        let format = Format::Csv(CsvOptions::default());
+        let csv = "user_id,labels\nbob,\n,\nalice,{\nzoe,[1]";
+        let users = decode::<User>(&format, &[csv.as_bytes()])?;
+        let rows: Vec<_> = users
+            .iter()
+            .map(|user| match user {
+                Ok(user) => Ok(user.user_id.as_str()),
+                Err(Error::InvalidRecord { row, .. }) => Err(*row),
+                Err(err) => panic!("unexpected error: {}", err),
+            })
+            .collect();
+        assert_eq!(rows, vec![Ok("bob"), Err(2), Err(3), Ok("zoe")]);
+
+        let lines = "{\"UserId\":\"bob\",\"Labels\":null,\"Comment\":\"\"}\n\nnot json\n";
+        let users: Vec<Result<User>> =
+            RecordReader::new(lines.as_bytes(), &Format::JsonLines).collect();
+        assert_eq!(users.len(), 2);
+        assert!(matches!(users[1], Err(Error::InvalidRecord { row: 3, .. })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_export() -> Result<()> {
+        let (entry_point, handle) = serve(4, |head, body| {
+            let body = if head.starts_with("post /api/feedback") {
+                let feedback: Vec<Feedback> = serde_json::from_slice(body).unwrap();
+                json!({ "RowAffected": feedback.len() })
+            } else if head.contains("cursor=&") {
+                json!({
+                    "Cursor": "next",
+                    "Feedback": [{"FeedbackType":"star","UserId":"bob","ItemId":"1","Value":1.0,"Timestamp":""}],
+                })
+            } else {
+                json!({ "Cursor": "", "Feedback": null })
+            };
+            test_server::json(&body.to_string())
+        });
+        let client = Gorse::builder()
+            .entry_point(entry_point)
+            .api_key("")
+            .batch_size(2)
+            .build()?;
+
+        let csv = "feedback_type,user_id,item_id\nstar,bob,1\nstar,,2\nlike,bob,2\nread,alice,3\n";
+        let mut batches = 0;
+        let report = client
+            .import::<Feedback>(csv.as_bytes(), &Format::Csv(CsvOptions::default()), |_| {
+                batches += 1
+            })
+            .await?;
+        assert_eq!(batches, 2);
+        assert_eq!(report.rows, 4);
+        assert_eq!(report.inserted, 3);
+        assert_eq!(
+            report.errors,
+            vec![RowError {
+                row: 2,
+                message: "missing user_id".into()
+            }]
+        );
+
+        let mut lines = Vec::new();
+        let rows = client
+            .export::<Feedback>(&mut lines, &Format::JsonLines, |_| {})
+            .await?;
+        assert_eq!(rows, 1);
+        assert_eq!(
+            String::from_utf8(lines).unwrap(),
+            "{\"FeedbackType\":\"star\",\"UserId\":\"bob\",\"ItemId\":\"1\",\"Value\":1.0,\"Timestamp\":\"\"}\n"
+        );
+        handle.join().unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_failure() -> Result<()> {
+        let mut requests = 0;
+        let (entry_point, handle) = serve(2, move |_, _| {
+            requests += 1;
+            if requests == 1 {
+                test_server::json(r#"{"RowAffected":2}"#)
+            } else {
+                test_server::response("500 Internal Server Error", "text/plain", "failed")
+            }
+        });
+        let client = Gorse::builder()
+            .entry_point(entry_point)
+            .api_key("")
+            .batch_size(2)
+            .build()?;
+
+        let csv = "feedback_type,user_id,item_id\nstar,bob,1\nlike,bob,2\nread,alice,3\n";
+        let err = client
+            .import::<Feedback>(csv.as_bytes(), &Format::Csv(CsvOptions::default()), |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(err.report.rows, 3);
+        assert_eq!(err.report.inserted, 2);
+        assert!(matches!(err.source, Error::Api { .. }));
+        handle.join().unwrap();
+        Ok(())
+    }
+}
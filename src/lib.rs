@@ -1,9 +1,76 @@
-use reqwest::Client;
+#[cfg(feature = "admin")]
+pub mod admin;
+mod builder;
+mod circuit;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compression;
+mod config;
+mod credentials;
+#[cfg(feature = "datasets")]
+pub mod datasets;
+mod endpoint;
+#[cfg(feature = "evaluation")]
+pub mod evaluation;
+pub mod experiment;
+mod fallback;
+#[cfg(feature = "io")]
+pub mod io;
+mod middleware;
+mod observer;
+mod recommender;
+mod response;
+#[cfg(feature = "tower")]
+pub mod service;
+pub mod split;
+mod stream;
+mod telemetry;
+#[cfg(test)]
+mod test_server;
+mod timestamp;
+#[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+mod tls;
+mod version;
+
+use std::sync::{Arc, OnceLock};
+
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder, Response};
 use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
 
+pub use builder::GorseBuilder;
+pub use circuit::{CircuitBreaker, CircuitState};
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub use compression::Compression;
+pub use config::GorseConfig;
+use credentials::Credentials;
+pub use credentials::{
+    ApiKey, CallbackCredentials, CredentialsProvider, EnvCredentials, FileCredentials,
+    StaticCredentials,
+};
+use endpoint::EndpointPool;
+pub use endpoint::LoadBalanceStrategy;
+use fallback::FallbackCollector;
+pub use fallback::{
+    Fallback, FallbackOptions, FallbackRecommendation, FallbackScore, FallbackSource,
+};
+use middleware::Middlewares;
+pub use middleware::{Middleware, RequestParts, ResponseParts};
+#[cfg(feature = "metrics")]
+pub use observer::MetricsObserver;
+use observer::Observers;
+pub use observer::{Observer, RequestInfo, RequestOutcome};
+use recommender::{Recommendation, Recommender};
+use response::{BodyRecord, Decode};
+#[cfg(feature = "blocking")]
+pub use stream::{JsonArrayIter, PageIter};
+pub use stream::{JsonArrayStream, PageStream};
+use telemetry::{RequestTelemetry, Rows};
+pub use version::{ServerInfo, ServerVersion};
+
 type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -52,6 +119,30 @@ pub struct RowAffected {
     pub row_affected: i32,
 }
 
+/// Page of a listing, e.g. from [`Gorse::get_users`]. The next page starts
+/// at `cursor`, which is empty after the last page.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(bound = "T: Deserialize<'de>")]
+pub struct Page<T> {
+    #[serde(rename = "Cursor")]
+    pub cursor: String,
+    #[serde(
+        alias = "Users",
+        alias = "Items",
+        alias = "Feedback",
+        deserialize_with = "null_as_empty"
+    )]
+    pub records: Vec<T>,
+}
+
+fn null_as_empty<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Score {
     #[serde(rename = "Id")]
@@ -60,6 +151,23 @@ pub struct Score {
     pub score: f64,
 }
 
+/// Health status reported by the `/api/health/*` endpoints.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct HealthStatus {
+    #[serde(rename = "Ready")]
+    pub ready: bool,
+    #[serde(rename = "DataStoreConnected")]
+    pub data_store_connected: bool,
+    #[serde(rename = "CacheStoreConnected")]
+    pub cache_store_connected: bool,
+    /// Error connecting to the data store, as serialized by the server.
+    #[serde(rename = "DataStoreError", default)]
+    pub data_store_error: Option<Value>,
+    /// Error connecting to the cache store, as serialized by the server.
+    #[serde(rename = "CacheStoreError", default)]
+    pub cache_store_error: Option<Value>,
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("API error: {status_code}: {message}")]
@@ -71,42 +179,96 @@ pub enum Error {
     Reqwest(#[from] reqwest::Error),
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
+    /// A successful response whose body could not be decoded.
+    #[error("cannot decode {status_code} response: {reason}, body: {body:?}")]
+    Decode {
+        status_code: StatusCode,
+        reason: String,
+        /// Start of the response body.
+        body: String,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("circuit breaker is open")]
+    CircuitOpen,
+    #[error("middleware error: {0}")]
+    Middleware(Box<dyn std::error::Error + Send + Sync>),
+    #[error("service error: {0}")]
+    Service(Box<dyn std::error::Error + Send + Sync>),
+    /// The server is too old for the call, see [`Gorse::server_info`].
+    #[error("{0}")]
+    Unsupported(String),
+    /// A record of a file that could not be read by the `io` module.
+    #[error("invalid record {row}: {message}")]
+    InvalidRecord { row: u64, message: String },
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone)]
 pub struct RecommendOptions {
     pub n: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct Gorse {
-    entry_point: String,
-    api_key: String,
-    client: Client,
+    pub(crate) endpoints: Arc<EndpointPool>,
+    pub(crate) credentials: Credentials,
+    pub(crate) client: Client,
+    pub(crate) circuit_breaker: Option<Arc<CircuitBreaker>>,
+    pub(crate) observers: Observers,
+    pub(crate) middlewares: Middlewares,
+    pub(crate) batch_size: Option<usize>,
+    pub(crate) server_info: Arc<OnceLock<ServerInfo>>,
+    pub(crate) probe_server_version: bool,
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub(crate) compression: Option<compression::RequestCompression>,
+    #[cfg(feature = "tower")]
+    pub(crate) service: Option<service::HttpService>,
 }
 
 impl Gorse {
+    /// Creates a client of a single endpoint. An endpoint the client cannot
+    /// connect to, e.g. HTTPS without a TLS feature, fails on each request;
+    /// use [`try_new`](Self::try_new) to reject it here.
     pub fn new(entry_point: impl Into<String>, api_key: impl Into<String>) -> Self {
-        let mut entry_point = entry_point.into();
-        if !entry_point.ends_with('/') {
-            entry_point.push('/');
-        }
-        Self {
-            entry_point,
-            api_key: api_key.into(),
-            client: Client::new(),
-        }
+        Self::builder()
+            .entry_point(entry_point)
+            .api_key(api_key)
+            .build_unchecked()
+            .expect("a single endpoint without TLS settings is always valid")
+    }
+
+    /// Creates a client of a single endpoint, failing with [`Error::Config`]
+    /// if the client cannot connect to it.
+    pub fn try_new(entry_point: impl Into<String>, api_key: impl Into<String>) -> Result<Self> {
+        Self::builder()
+            .entry_point(entry_point)
+            .api_key(api_key)
+            .build()
+    }
+
+    /// Creates a builder to configure a client, e.g. with several endpoints.
+    pub fn builder() -> GorseBuilder {
+        GorseBuilder::new()
+    }
+
+    /// Creates a client configured by environment variables, see
+    /// [`GorseConfig::from_env`].
+    pub fn from_env() -> Result<Self> {
+        GorseConfig::from_env()?.build()
     }
 
     pub async fn insert_user(&self, user: &User) -> Result<RowAffected> {
-        self.request(Method::POST, format!("{}api/user", self.entry_point), user)
+        self.request("insert_user", Method::POST, "api/user".into(), user)
             .await
     }
 
     pub async fn get_user(&self, user_id: &str) -> Result<User> {
         self.request::<(), User>(
+            "get_user",
             Method::GET,
-            format!("{}api/user/{}", self.entry_point, user_id),
+            format!("api/user/{}", user_id),
             &(),
         )
         .await
@@ -114,22 +276,44 @@ impl Gorse {
 
     pub async fn delete_user(&self, user_id: &str) -> Result<RowAffected> {
         self.request::<(), RowAffected>(
+            "delete_user",
             Method::DELETE,
-            format!("{}api/user/{}", self.entry_point, user_id),
+            format!("api/user/{}", user_id),
+            &(),
+        )
+        .await
+    }
+
+    /// Inserts users, in batches of the configured
+    /// [`batch_size`](GorseBuilder::batch_size). Batches sent before a failed
+    /// batch stay inserted.
+    pub async fn insert_users(&self, users: &[User]) -> Result<RowAffected> {
+        self.insert_batches("insert_users", "api/users", users)
+            .await
+    }
+
+    /// Lists users by pages of `n`, starting at `cursor`, which is empty for
+    /// the first page.
+    pub async fn get_users(&self, cursor: &str, n: usize) -> Result<Page<User>> {
+        self.request::<(), Page<User>>(
+            "get_users",
+            Method::GET,
+            page_path("api/users", cursor, n),
             &(),
         )
         .await
     }
 
     pub async fn insert_item(&self, item: &Item) -> Result<RowAffected> {
-        self.request(Method::POST, format!("{}api/item", self.entry_point), item)
+        self.request("insert_item", Method::POST, "api/item".into(), item)
             .await
     }
 
     pub async fn get_item(&self, item_id: &str) -> Result<Item> {
         self.request::<(), Item>(
+            "get_item",
             Method::GET,
-            format!("{}api/item/{}", self.entry_point, item_id),
+            format!("api/item/{}", item_id),
             &(),
         )
         .await
@@ -137,35 +321,65 @@ impl Gorse {
 
     pub async fn delete_item(&self, item_id: &str) -> Result<RowAffected> {
         self.request::<(), RowAffected>(
+            "delete_item",
             Method::DELETE,
-            format!("{}api/item/{}", self.entry_point, item_id),
+            format!("api/item/{}", item_id),
             &(),
         )
         .await
     }
 
-    pub async fn insert_feedback(&self, feedback: &[Feedback]) -> Result<RowAffected> {
-        self.request(
-            Method::POST,
-            format!("{}api/feedback", self.entry_point),
-            feedback,
+    /// Inserts items, in batches of the configured
+    /// [`batch_size`](GorseBuilder::batch_size). Batches sent before a failed
+    /// batch stay inserted.
+    pub async fn insert_items(&self, items: &[Item]) -> Result<RowAffected> {
+        self.insert_batches("insert_items", "api/items", items)
+            .await
+    }
+
+    /// Lists items by pages of `n`, starting at `cursor`, which is empty for
+    /// the first page.
+    pub async fn get_items(&self, cursor: &str, n: usize) -> Result<Page<Item>> {
+        self.request::<(), Page<Item>>(
+            "get_items",
+            Method::GET,
+            page_path("api/items", cursor, n),
+            &(),
         )
         .await
     }
 
+    /// Inserts feedback, in batches of the configured
+    /// [`batch_size`](GorseBuilder::batch_size). Batches sent before a failed
+    /// batch stay inserted.
+    pub async fn insert_feedback(&self, feedback: &[Feedback]) -> Result<RowAffected> {
+        self.insert_batches("insert_feedback", "api/feedback", feedback)
+            .await
+    }
+
+    // Takes a `Vec` for compatibility with existing callers.
+    #[allow(clippy::ptr_arg)]
     pub async fn upsert_feedback(&self, feedback: &Vec<Feedback>) -> Result<RowAffected> {
-        self.request(
-            Method::PUT,
-            format!("{}api/feedback", self.entry_point),
-            feedback,
-        )
-        .await
+        let mut total = RowAffected { row_affected: 0 };
+        for batch in batches(feedback, self.batch_size) {
+            total.row_affected += self
+                .request::<[Feedback], RowAffected>(
+                    "upsert_feedback",
+                    Method::PUT,
+                    "api/feedback".into(),
+                    batch,
+                )
+                .await?
+                .row_affected;
+        }
+        Ok(total)
     }
 
     pub async fn delete_feedback(&self, user_id: &str, item_id: &str) -> Result<RowAffected> {
         self.request::<(), RowAffected>(
+            "delete_feedback",
             Method::DELETE,
-            format!("{}api/feedback/{}/{}", self.entry_point, user_id, item_id),
+            format!("api/feedback/{}/{}", user_id, item_id),
             &(),
         )
         .await
@@ -173,77 +387,694 @@ impl Gorse {
 
     pub async fn list_feedback(&self, user_id: &str, feedback_type: &str) -> Result<Vec<Feedback>> {
         self.request::<(), Vec<Feedback>>(
+            "list_feedback",
+            Method::GET,
+            format!("api/user/{}/feedback/{}", user_id, feedback_type),
+            &(),
+        )
+        .await
+    }
+
+    /// Lists the feedback of all users by pages of `n`, starting at `cursor`,
+    /// which is empty for the first page.
+    pub async fn get_feedback(&self, cursor: &str, n: usize) -> Result<Page<Feedback>> {
+        self.request::<(), Page<Feedback>>(
+            "get_feedback",
             Method::GET,
-            format!(
-                "{}api/user/{}/feedback/{}",
-                self.entry_point, user_id, feedback_type
-            ),
+            page_path("api/feedback", cursor, n),
             &(),
         )
         .await
     }
 
+    /// Streams the feedback of a user, decoding each record as it arrives
+    /// instead of buffering the whole response.
+    pub async fn stream_feedback(
+        &self,
+        user_id: &str,
+        feedback_type: &str,
+    ) -> Result<JsonArrayStream<Feedback>> {
+        self.request_stream(
+            "stream_feedback",
+            format!("api/user/{}/feedback/{}", user_id, feedback_type),
+        )
+        .await
+    }
+
+    /// Streams the feedback of all users, requested by pages of `n` as the
+    /// records are read and decoded as they arrive.
+    pub fn stream_all_feedback(&self, n: usize) -> PageStream<Feedback> {
+        PageStream::new(self.clone(), "get_feedback", "api/feedback", n)
+    }
+
     pub async fn get_item_neighbors(&self, item_id: &str) -> Result<Vec<Score>> {
         self.request::<(), Vec<Score>>(
+            "get_item_neighbors",
             Method::GET,
-            format!("{}api/item/{}/neighbors", self.entry_point, item_id),
+            format!("api/item/{}/neighbors", item_id),
             &(),
         )
         .await
     }
 
     /// Get recommendation with scores for a user.
-    /// Uses X-API-Version: 2 header to return scores.
+    /// Uses X-API-Version: 2 header to return scores. Servers before 0.5
+    /// only return item ids, see [`get_recommend_ids`](Self::get_recommend_ids),
+    /// and fail with [`Error::Unsupported`].
     pub async fn get_recommend(
         &self,
         user_id: &str,
         options: RecommendOptions,
     ) -> Result<Vec<Score>> {
-        let mut url = format!("{}api/recommend/{}", self.entry_point, user_id);
-        if options.n > 0 {
-            url = format!("{}?n={}", url, options.n);
+        let server = self.negotiated_server_info().await?;
+        if let Some(server) = server.filter(|server| !server.has_scored_recommend()) {
+            return Err(server.unsupported("recommendation with scores"));
+        }
+        match self.request_recommend(user_id, options, true).await? {
+            Recommendation::Scored(scores) => Ok(scores),
+            Recommendation::Ids(_) => {
+                Err(ServerInfo::legacy().unsupported("recommendation with scores"))
+            }
+        }
+    }
+
+    /// Get recommended item ids for a user, from servers of any version.
+    pub async fn get_recommend_ids(
+        &self,
+        user_id: &str,
+        options: RecommendOptions,
+    ) -> Result<Vec<String>> {
+        let scored = self
+            .negotiated_server_info()
+            .await?
+            .is_none_or(|server| server.has_scored_recommend());
+        Ok(self
+            .request_recommend(user_id, options, scored)
+            .await?
+            .into_ids())
+    }
+
+    /// Get popular items, optionally restricted to a category.
+    pub async fn get_popular(
+        &self,
+        category: Option<&str>,
+        options: RecommendOptions,
+    ) -> Result<Vec<Score>> {
+        self.request::<(), Vec<Score>>(
+            "get_popular",
+            Method::GET,
+            non_personalized_path("api/popular", category, &options),
+            &(),
+        )
+        .await
+    }
+
+    /// Get latest items, optionally restricted to a category.
+    pub async fn get_latest(
+        &self,
+        category: Option<&str>,
+        options: RecommendOptions,
+    ) -> Result<Vec<Score>> {
+        self.request::<(), Vec<Score>>(
+            "get_latest",
+            Method::GET,
+            non_personalized_path("api/latest", category, &options),
+            &(),
+        )
+        .await
+    }
+
+    /// Get items similar to an item from a named item-to-item recommender.
+    /// Servers older than 0.5 have a single recommender, used whatever the
+    /// name.
+    pub async fn get_item_to_item(
+        &self,
+        name: &str,
+        item_id: &str,
+        options: RecommendOptions,
+    ) -> Result<Vec<Score>> {
+        self.recommend(Recommender::ItemToItem { name, item_id }, options)
+            .await
+    }
+
+    /// Get users similar to a user from a named user-to-user recommender.
+    /// Servers older than 0.5 have a single recommender, used whatever the
+    /// name.
+    pub async fn get_user_to_user(
+        &self,
+        name: &str,
+        user_id: &str,
+        options: RecommendOptions,
+    ) -> Result<Vec<Score>> {
+        self.recommend(Recommender::UserToUser { name, user_id }, options)
+            .await
+    }
+
+    /// Get the collaborative filtering recommendation of a user, before it
+    /// is merged with the other recommenders.
+    pub async fn get_collaborative_filtering(
+        &self,
+        user_id: &str,
+        options: RecommendOptions,
+    ) -> Result<Vec<Score>> {
+        self.recommend(Recommender::CollaborativeFiltering { user_id }, options)
+            .await
+    }
+
+    /// Get items from a named non-personalized recommender, e.g. `popular`.
+    /// Servers older than 0.5 only have `popular` and `latest`.
+    pub async fn get_non_personalized(
+        &self,
+        name: &str,
+        options: RecommendOptions,
+    ) -> Result<Vec<Score>> {
+        self.recommend(Recommender::NonPersonalized { name }, options)
+            .await
+    }
+
+    /// Get recommendation for a user, trying the sources in
+    /// `options.strategies` in order until `options.n` items are collected.
+    /// Sources that fail are skipped and reported in the result.
+    pub async fn recommend_with_fallback(
+        &self,
+        user_id: &str,
+        options: &FallbackOptions,
+    ) -> FallbackRecommendation {
+        let mut collector = FallbackCollector::new(options);
+        for strategy in &options.strategies {
+            if collector.is_full() {
+                break;
+            }
+            let fetch = RecommendOptions {
+                n: collector.fetch_size(),
+            };
+            let result = match strategy {
+                Fallback::Personalized => self.get_recommend(user_id, fetch).await,
+                Fallback::CategoryPopular(category) => {
+                    self.get_popular(Some(category), fetch).await
+                }
+                Fallback::Popular => self.get_popular(None, fetch).await,
+                Fallback::CategoryLatest(category) => self.get_latest(Some(category), fetch).await,
+                Fallback::Latest => self.get_latest(None, fetch).await,
+                Fallback::Static(_) => Ok(Vec::new()),
+            };
+            collector.collect(strategy, result);
+        }
+        collector.finish()
+    }
+
+    /// Returns the version and capabilities of the server. The server is
+    /// probed once, and the result is shared by the clones of the client.
+    /// The capabilities of servers that do not report their version are
+    /// detected from the recommendation route; until it recommends items,
+    /// such servers are assumed to be current and probed again.
+    pub async fn server_info(&self) -> Result<ServerInfo> {
+        if let Some(server) = self.server_info.get() {
+            return Ok(server.clone());
+        }
+        let server = match self
+            .request::<(), ServerInfo>("server_info", Method::GET, "api/version".into(), &())
+            .await
+        {
+            Err(Error::Api {
+                status_code: StatusCode::NOT_FOUND,
+                ..
+            }) => match detected_server_info(
+                self.request_recommend(PROBE_USER_ID, RecommendOptions { n: 1 }, true)
+                    .await,
+            )? {
+                Some(server) => server,
+                None => return Ok(ServerInfo::unreported(true)),
+            },
+            r => r?,
+        };
+        Ok(self.server_info.get_or_init(|| server).clone())
+    }
+
+    /// Checks whether the server is alive.
+    pub async fn health_live(&self) -> Result<HealthStatus> {
+        self.request::<(), HealthStatus>("health_live", Method::GET, "api/health/live".into(), &())
+            .await
+    }
+
+    /// Checks whether the server and its data stores are ready. A server that
+    /// is not ready responds with `503 Service Unavailable`, which is returned
+    /// as a status with `ready` set to false rather than as an error. Other
+    /// `503` responses, e.g. from a proxy, are errors.
+    pub async fn health_ready(&self) -> Result<HealthStatus> {
+        not_ready_status(
+            self.request::<(), HealthStatus>(
+                "health_ready",
+                Method::GET,
+                "api/health/ready".into(),
+                &(),
+            )
+            .await,
+        )
+    }
+
+    /// Server the routes and decoding of version dependent calls are chosen
+    /// for: probed if [`GorseBuilder::probe_server_version`] is set, else the
+    /// configured version, if any. `None` stands for the latest server.
+    async fn negotiated_server_info(&self) -> Result<Option<ServerInfo>> {
+        if self.probe_server_version {
+            Ok(Some(self.server_info().await?))
+        } else {
+            Ok(self.server_info.get().cloned())
+        }
+    }
+
+    async fn recommend(
+        &self,
+        recommender: Recommender<'_>,
+        options: RecommendOptions,
+    ) -> Result<Vec<Score>> {
+        let server = self.negotiated_server_info().await?;
+        self.request::<(), Vec<Score>>(
+            recommender.endpoint(),
+            Method::GET,
+            recommender.path(server.as_ref(), &options)?,
+            &(),
+        )
+        .await
+    }
+
+    async fn request_recommend(
+        &self,
+        user_id: &str,
+        options: RecommendOptions,
+        scored: bool,
+    ) -> Result<Recommendation> {
+        self.request_with_headers::<(), Recommendation>(
+            "get_recommend",
+            Method::GET,
+            with_options(format!("api/recommend/{}", user_id), &options),
+            &(),
+            scored.then_some("2"),
+        )
+        .await
+    }
+
+    async fn insert_batches<T: Serialize>(
+        &self,
+        endpoint: &'static str,
+        path: &str,
+        records: &[T],
+    ) -> Result<RowAffected> {
+        let mut total = RowAffected { row_affected: 0 };
+        for batch in batches(records, self.batch_size) {
+            total.row_affected += self
+                .request::<[T], RowAffected>(endpoint, Method::POST, path.into(), batch)
+                .await?
+                .row_affected;
         }
-        self.request_with_headers::<(), Vec<Score>>(Method::GET, url, &(), Some("2")).await
+        Ok(total)
     }
 
-    async fn request<BodyType: Serialize + ?Sized, RetType: for<'a> Deserialize<'a>>(
+    async fn request<BodyType: Serialize + Rows + ?Sized, RetType: Decode + Rows>(
         &self,
+        endpoint: &'static str,
         method: Method,
-        url: String,
+        path: String,
         body: &BodyType,
     ) -> Result<RetType> {
-        self.request_with_headers(method, url, body, None).await
+        self.request_with_headers(endpoint, method, path, body, None)
+            .await
     }
 
-    async fn request_with_headers<BodyType: Serialize + ?Sized, RetType: for<'a> Deserialize<'a>>(
+    async fn request_with_headers<BodyType: Serialize + Rows + ?Sized, RetType: Decode + Rows>(
         &self,
+        endpoint: &'static str,
         method: Method,
-        url: String,
+        path: String,
         body: &BodyType,
         api_version: Option<&str>,
     ) -> Result<RetType> {
-        let mut request = self
-            .client
-            .request(method, url)
-            .header("X-API-Key", self.api_key.as_str())
-            .header("Content-Type", "application/json")
-            .json(body);
-        
-        if let Some(version) = api_version {
-            request = request.header("X-API-Version", version);
-        }
-        
-        let response = request.send().await?;
-        if response.status() == StatusCode::OK {
-            let r: RetType = serde_json::from_str(response.text().await?.as_str())?;
-            Ok(r)
+        let bytes = serde_json::to_vec(body)?;
+        let body_size = bytes.len();
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        let (bytes, content_encoding) = compression::compress(self.compression, bytes)?;
+        #[cfg(not(any(feature = "gzip", feature = "zstd")))]
+        let content_encoding = None;
+        let telemetry = RequestTelemetry::start(
+            endpoint,
+            &method,
+            bytes.len(),
+            body_size,
+            body.rows(),
+            &self.observers,
+        );
+        let headers = RequestHeaders {
+            api_version,
+            content_encoding,
+        };
+        let result = telemetry
+            .instrument(self.execute(&telemetry, method, path, bytes, headers))
+            .await;
+        telemetry.finish(result.as_ref());
+        result
+    }
+
+    async fn execute<RetType: Decode>(
+        &self,
+        telemetry: &RequestTelemetry<'_>,
+        method: Method,
+        path: String,
+        body: Vec<u8>,
+        headers: RequestHeaders<'_>,
+    ) -> Result<RetType> {
+        let response = self.open(telemetry, method, &path, body, headers).await?;
+        let status_code = response.status();
+        let mut record = BodyRecord::default();
+        let result = response::read_body(response, &mut record).await;
+        telemetry.record_response(status_code, record.count);
+        result
+    }
+
+    /// Sends a request guarded by the circuit breaker and returns the
+    /// response if it is successful, leaving its body unread.
+    async fn open(
+        &self,
+        telemetry: &RequestTelemetry<'_>,
+        method: Method,
+        path: &str,
+        body: Vec<u8>,
+        headers: RequestHeaders<'_>,
+    ) -> Result<Response> {
+        let mut permit = match &self.circuit_breaker {
+            Some(circuit_breaker) if !is_health_check(path) => Some(circuit_breaker.acquire()?),
+            _ => None,
+        };
+        let response = self.send(telemetry, method, path, body, headers).await;
+        if let Some(permit) = permit.as_mut() {
+            match &response {
+                Ok(_) => permit.record(true),
+                Err(err) if is_server_failure(err) => permit.record(false),
+                // Dropped without an outcome, which leaves the breaker as it
+                // was.
+                Err(_) => {}
+            }
+        }
+        let response = response?;
+        let status_code = response.status();
+        if status_code.is_success() {
+            return Ok(response);
+        }
+        let text = response.text().await?;
+        telemetry.record_response(status_code, text.len());
+        Err(Error::Api {
+            status_code,
+            message: text,
+        })
+    }
+
+    /// Sends a GET request and streams the elements of the JSON array it
+    /// returns. Observers are notified once the response headers are received.
+    async fn request_stream<RetType: DeserializeOwned>(
+        &self,
+        endpoint: &'static str,
+        path: String,
+    ) -> Result<JsonArrayStream<RetType>> {
+        Ok(JsonArrayStream::new(
+            self.open_stream(endpoint, path).await?,
+        ))
+    }
+
+    /// Sends a GET request and returns the response of a JSON body, leaving
+    /// it unread.
+    async fn open_stream(&self, endpoint: &'static str, path: String) -> Result<Response> {
+        let method = Method::GET;
+        let telemetry = RequestTelemetry::start(endpoint, &method, 0, 0, None, &self.observers);
+        let result = telemetry
+            .instrument(self.open(
+                &telemetry,
+                method,
+                &path,
+                Vec::new(),
+                RequestHeaders::default(),
+            ))
+            .await;
+        telemetry.finish(result.as_ref().map(|_| &()));
+        let response = result?;
+        response::check_content_type(response.status(), response.headers(), &[])?;
+        Ok(response)
+    }
+
+    /// Sends a request to the endpoints in the order chosen by the load
+    /// balancing strategy. Reads fail over to the next endpoint on connection
+    /// errors and server errors, and are retried after a backoff once every
+    /// endpoint failed; writes are only attempted once. Health checks
+    /// return server errors, e.g. `503` from a server that is not ready, and
+    /// leave the health of endpoints unchanged.
+    async fn send(
+        &self,
+        telemetry: &RequestTelemetry<'_>,
+        method: Method,
+        path: &str,
+        body: Vec<u8>,
+        headers: RequestHeaders<'_>,
+    ) -> Result<Response> {
+        let failover = method == Method::GET || method == Method::HEAD;
+        let health_check = is_health_check(path);
+        let headers = request_headers(&self.credentials, headers)?;
+        self.spawn_probes();
+        let retry = self.endpoints.retry();
+        let rounds = if failover { retry.max_retries } else { 0 };
+        let mut last_error = None;
+        for round in 0..=rounds {
+            if round > 0 {
+                tokio::time::sleep(retry.delay(round)).await;
+            }
+            for index in self.endpoints.candidates() {
+                if let Some(error) = &last_error {
+                    telemetry.record_retry(error);
+                }
+                let _outstanding = self.endpoints.begin(index);
+                let mut request = RequestParts {
+                    method: method.clone(),
+                    url: format!("{}{}", self.endpoints.url(index), path),
+                    headers: headers.clone(),
+                    body: body.clone(),
+                };
+                self.middlewares.on_request(&mut request)?;
+
+                let result = self
+                    .dispatch(
+                        self.client
+                            .request(request.method.clone(), request.url.as_str())
+                            .headers(request.headers)
+                            .body(request.body),
+                    )
+                    .await;
+                let error = match result {
+                    Ok(response) => {
+                        self.middlewares.on_response(&ResponseParts {
+                            method: &request.method,
+                            url: &request.url,
+                            status: response.status(),
+                            headers: response.headers(),
+                        });
+                        if health_check {
+                            return Ok(response);
+                        }
+                        if !response.status().is_server_error() {
+                            self.endpoints.mark_success(index);
+                            return Ok(response);
+                        }
+                        self.endpoints.mark_failure(index);
+                        Error::Api {
+                            status_code: response.status(),
+                            // A body that cannot be read must not stop the
+                            // failover.
+                            message: response.text().await.unwrap_or_default(),
+                        }
+                    }
+                    Err(err) => {
+                        self.middlewares
+                            .on_error(&request.method, &request.url, &err);
+                        if !health_check {
+                            self.endpoints.mark_failure(index);
+                        }
+                        err
+                    }
+                };
+                last_error = Some(error);
+                if !failover {
+                    break;
+                }
+            }
+        }
+        Err(last_error.expect("endpoint pool is never empty"))
+    }
+
+    /// Probes unhealthy endpoints that are due in a background task, which
+    /// brings them back on success, so that requests do not wait for probes.
+    fn spawn_probes(&self) {
+        let due = self.endpoints.due_for_probe();
+        if due.is_empty() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let client = self.clone();
+        runtime.spawn(async move {
+            for index in due {
+                let probe = client.probe(index).await;
+                if matches!(probe, Ok(response) if response.status().is_success()) {
+                    client.endpoints.mark_success(index);
+                }
+            }
+        });
+    }
+
+    async fn probe(&self, index: usize) -> Result<Response> {
+        let mut request = RequestParts {
+            method: Method::GET,
+            url: format!("{}api/health/live", self.endpoints.url(index)),
+            headers: request_headers(&self.credentials, RequestHeaders::default())?,
+            body: Vec::new(),
+        };
+        self.middlewares.on_request(&mut request)?;
+        self.dispatch(
+            self.client
+                .request(request.method, request.url)
+                .headers(request.headers)
+                .timeout(endpoint::PROBE_TIMEOUT),
+        )
+        .await
+    }
+
+    /// Sends a request through the tower service if one was configured, or
+    /// with the reqwest client otherwise.
+    async fn dispatch(&self, request: RequestBuilder) -> Result<Response> {
+        #[cfg(feature = "tower")]
+        if let Some(service) = &self.service {
+            return service.call(request.build()?).await;
+        }
+        Ok(request.send().await?)
+    }
+}
+
+/// Headers that vary between requests.
+#[derive(Clone, Copy, Default)]
+struct RequestHeaders<'a> {
+    api_version: Option<&'a str>,
+    content_encoding: Option<&'static str>,
+}
+
+/// Headers sent with every request: the API key, which is marked sensitive,
+/// the content type and encoding, the API version and the trace context.
+fn request_headers(credentials: &Credentials, extra: RequestHeaders<'_>) -> Result<HeaderMap> {
+    let mut headers = telemetry::trace_context();
+    let mut key = HeaderValue::from_str(credentials.0.api_key()?.expose())
+        .map_err(|_| Error::Config("API key is not a valid header value".into()))?;
+    key.set_sensitive(true);
+    headers.insert("X-API-Key", key);
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    if let Some(encoding) = extra.content_encoding {
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+    }
+    if let Some(version) = extra.api_version {
+        headers.insert(
+            "X-API-Version",
+            HeaderValue::from_str(version)
+                .map_err(|_| Error::Config("API version is not a valid header value".into()))?,
+        );
+    }
+    Ok(headers)
+}
+
+/// Turns the `503` response of a server that is not ready into its status.
+fn not_ready_status(result: Result<HealthStatus>) -> Result<HealthStatus> {
+    match result {
+        Err(Error::Api {
+            status_code: StatusCode::SERVICE_UNAVAILABLE,
+            message,
+        }) => match serde_json::from_str(&message) {
+            Ok(status) => Ok(status),
+            Err(_) => Err(Error::Api {
+                status_code: StatusCode::SERVICE_UNAVAILABLE,
+                message,
+            }),
+        },
+        r => r,
+    }
+}
+
+/// User whose recommendation tells apart servers that do not report their
+/// version. Any user will do, as servers fill the recommendation of unknown
+/// users with their fallback recommenders.
+const PROBE_USER_ID: &str = "gorse-rs-probe";
+
+/// Capabilities of a server that does not report its version, from a
+/// recommendation of [`PROBE_USER_ID`]. `None` if nothing is recommended.
+fn detected_server_info(result: Result<Recommendation>) -> Result<Option<ServerInfo>> {
+    match result {
+        Err(Error::Api {
+            status_code: StatusCode::NOT_FOUND,
+            ..
+        }) => Ok(None),
+        r => Ok(r?.server_info()),
+    }
+}
+
+/// Whether an error of a request tells of the health of the server, which
+/// could not be reached or failed with a `5xx` status, unlike errors raised
+/// before sending the request, e.g. by credentials providers or middleware.
+fn is_server_failure(error: &Error) -> bool {
+    match error {
+        Error::Reqwest(_) | Error::Service(_) => true,
+        Error::Api { status_code, .. } => status_code.is_server_error(),
+        _ => false,
+    }
+}
+
+/// Whether a request is a health check, which reports the health of the
+/// server rather than fails with it.
+fn is_health_check(path: &str) -> bool {
+    path.starts_with("api/health/")
+}
+
+fn page_path(path: &str, cursor: &str, n: usize) -> String {
+    let mut cursor_param = String::new();
+    for byte in cursor.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            cursor_param.push(byte as char);
         } else {
-            Err(Error::Api {
-                status_code: response.status(),
-                message: response.text().await?,
-            })
+            cursor_param.push_str(&format!("%{:02X}", byte));
         }
     }
+    format!("{}?cursor={}&n={}", path, cursor_param, n)
+}
+
+/// Splits the records of a batch endpoint into batches of at most `batch_size`
+/// records. An empty input still yields one empty batch.
+fn batches<T>(records: &[T], batch_size: Option<usize>) -> impl Iterator<Item = &[T]> {
+    let size = batch_size.unwrap_or(records.len()).max(1);
+    records
+        .chunks(size)
+        .chain(records.is_empty().then_some(records))
+}
+
+fn non_personalized_path(
+    prefix: &str,
+    category: Option<&str>,
+    options: &RecommendOptions,
+) -> String {
+    let path = match category {
+        Some(category) => format!("{}/{}", prefix, category),
+        None => prefix.to_string(),
+    };
+    with_options(path, options)
+}
+
+fn with_options(path: String, options: &RecommendOptions) -> String {
+    if options.n > 0 {
+        format!("{}?n={}", path, options.n)
+    } else {
+        path
+    }
 }
 
 #[cfg(test)]
@@ -251,6 +1082,8 @@ mod tests {
     use super::*;
     use serde_json::json;
     use serial_test::serial;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
 
     const ENTRY_POINT: &str = "http://127.0.0.1:8088/";
     const API_KEY: &str = "zhenghaoz";
@@ -399,177 +1232,1206 @@ mod tests {
         assert_eq!(items[2].id, "918");
         Ok(())
     }
-}
-
-#[cfg(feature = "blocking")]
-pub mod blocking {
-    use reqwest::blocking::Client;
-    use serde::{Deserialize, Serialize};
-
-    use crate::{
-        Error, Feedback, Item, Method, RecommendOptions, Result, RowAffected, Score, StatusCode,
-        User,
-    };
 
-    #[derive(Debug, Clone)]
-    pub struct Gorse {
-        entry_point: String,
-        api_key: String,
-        client: Client,
+    #[tokio::test]
+    #[serial]
+    async fn test_failover() -> Result<()> {
+        let client = Gorse::builder()
+            .endpoints(["http://127.0.0.1:1/", ENTRY_POINT])
+            .api_key(API_KEY)
+            .failure_threshold(1)
+            .build()?;
+        for _ in 0..3 {
+            let scores = client.get_item_neighbors("1").await?;
+            assert_eq!(scores[0].id, "1060".to_string());
+        }
+        Ok(())
     }
 
-    impl Gorse {
-        pub fn new(entry_point: impl Into<String>, api_key: impl Into<String>) -> Self {
-            let mut entry_point = entry_point.into();
-            if !entry_point.ends_with('/') {
-                entry_point.push('/');
+    #[tokio::test]
+    #[serial]
+    async fn test_health() -> Result<()> {
+        let client = Gorse::new(ENTRY_POINT, API_KEY);
+        let status = client.health_live().await?;
+        assert!(status.ready);
+        let status = client.health_ready().await?;
+        assert!(status.ready);
+        assert!(status.data_store_connected);
+        assert!(status.cache_store_connected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_circuit_breaker() -> Result<()> {
+        let client = Gorse::builder()
+            .entry_point("http://127.0.0.1:1/")
+            .circuit_breaker(CircuitBreaker::new().minimum_calls(1))
+            .build()?;
+        assert!(matches!(
+            client.get_item_neighbors("1").await,
+            Err(Error::Reqwest(_))
+        ));
+        assert!(matches!(
+            client.get_item_neighbors("1").await,
+            Err(Error::CircuitOpen)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_ignores_local_errors() -> Result<()> {
+        let client = Gorse::builder()
+            .entry_point("http://127.0.0.1:1/")
+            .credentials(FileCredentials::new("/nonexistent/gorse_api_key"))
+            .circuit_breaker(CircuitBreaker::new().minimum_calls(1))
+            .build()?;
+        for _ in 0..3 {
+            assert!(matches!(client.get_item("1").await, Err(Error::Config(_))));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_fallback() -> Result<()> {
+        let client = Gorse::new(ENTRY_POINT, API_KEY);
+        let popular = client.get_popular(None, RecommendOptions { n: 3 }).await?;
+        assert_eq!(popular.len(), 3);
+        let latest = client.get_latest(None, RecommendOptions { n: 3 }).await?;
+        assert_eq!(latest.len(), 3);
+
+        let options = FallbackOptions {
+            n: 6,
+            strategies: vec![
+                Fallback::Personalized,
+                Fallback::Popular,
+                Fallback::Static(vec!["1".into()]),
+            ],
+            exclude: vec![popular[0].id.clone()],
+        };
+        let recommendation = client.recommend_with_fallback("not-exist", &options).await;
+        assert_eq!(recommendation.items.len(), 6);
+        assert!(recommendation
+            .items
+            .iter()
+            .all(|item| item.id != popular[0].id));
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct CountingObserver {
+        starts: AtomicUsize,
+        retries: AtomicUsize,
+        attempts: AtomicUsize,
+    }
+
+    impl Observer for CountingObserver {
+        fn on_request_start(&self, request: &RequestInfo) {
+            assert_eq!(request.endpoint, "get_item_neighbors");
+            self.starts.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_retry(&self, _request: &RequestInfo, _error: &Error) {
+            self.retries.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_request_finish(&self, _request: &RequestInfo, outcome: &RequestOutcome<'_>) {
+            assert!(outcome.error.is_some());
+            self.attempts.fetch_add(outcome.attempts, Ordering::Relaxed);
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_observer() -> Result<()> {
+        let observer = Arc::new(CountingObserver::default());
+        let client = Gorse::builder()
+            .endpoints(["http://127.0.0.1:1/", "http://127.0.0.1:2/"])
+            .observer(observer.clone())
+            .build()?;
+        assert!(client.get_item_neighbors("1").await.is_err());
+        assert_eq!(observer.starts.load(Ordering::Relaxed), 1);
+        assert_eq!(observer.retries.load(Ordering::Relaxed), 1);
+        assert_eq!(observer.attempts.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    struct Rewrite;
+
+    impl Middleware for Rewrite {
+        fn on_request(&self, request: &mut RequestParts) -> Result<()> {
+            if request.url.contains("/denied/") {
+                return Err(Error::Middleware("denied".into()));
             }
-            Self {
-                entry_point,
-                api_key: api_key.into(),
-                client: Client::new(),
+            request.url = request.url.replace("http://127.0.0.1:1/", ENTRY_POINT);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_middleware() -> Result<()> {
+        let client = Gorse::builder()
+            .entry_point("http://127.0.0.1:1/")
+            .api_key(API_KEY)
+            .middleware(Rewrite)
+            .build()?;
+        assert!(matches!(
+            client.get_item_neighbors("denied").await,
+            Err(Error::Middleware(_))
+        ));
+        let scores = client.get_item_neighbors("1").await?;
+        assert_eq!(scores[0].id, "1060".to_string());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_background_probe() -> Result<()> {
+        // Accepts connections but never responds.
+        let silent = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let (entry_point, server) =
+            test_server::serve(2, |_, _| test_server::json(r#"[{"Id":"1","Score":1}]"#));
+        let client = Gorse::builder()
+            .endpoints([format!("http://{}/", silent.local_addr()?), entry_point])
+            .failure_threshold(1)
+            .probe_interval(Duration::ZERO)
+            .timeout(Duration::from_millis(200))
+            .build()?;
+        client.get_item_neighbors("1").await?;
+        // The silent endpoint is probed without delaying the request.
+        let start = Instant::now();
+        client.get_item_neighbors("1").await?;
+        assert!(start.elapsed() < endpoint::PROBE_TIMEOUT);
+        server.join().unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_health_not_ready() -> Result<()> {
+        let (entry_point, server) = test_server::serve(3, |_, _| {
+            test_server::response(
+                "503 Service Unavailable",
+                "application/json",
+                r#"{"Ready":false,"DataStoreConnected":false,"CacheStoreConnected":true}"#,
+            )
+        });
+        let client = Gorse::builder()
+            .entry_point(entry_point)
+            .failure_threshold(1)
+            .probe_interval(Duration::ZERO)
+            .circuit_breaker(CircuitBreaker::new().minimum_calls(1))
+            .build()?;
+        for _ in 0..3 {
+            assert!(!client.health_ready().await?.ready);
+        }
+        // Neither the endpoint nor the circuit breaker count the 503s.
+        assert!(client.endpoints.due_for_probe().is_empty());
+        assert_eq!(
+            client.circuit_breaker.as_ref().unwrap().state(),
+            CircuitState::Closed
+        );
+        server.join().unwrap();
+        Ok(())
+    }
+
+    /// Failed responses of [`flaky_server`]: a `503`, and a `503` whose body
+    /// ends before its length.
+    pub(crate) fn failures() -> [String; 2] {
+        [
+            test_server::response("503 Service Unavailable", "text/plain", ""),
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 100\r\n\r\ntruncated".into(),
+        ]
+    }
+
+    /// Serves a failed response and then an item.
+    pub(crate) fn flaky_server(failure: String) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let mut failed = false;
+        test_server::serve(2, move |_, _| {
+            if std::mem::replace(&mut failed, true) {
+                test_server::json(
+                    r#"{"ItemId":"1","IsHidden":false,"Labels":[],"Categories":[],"Timestamp":"","Comment":""}"#,
+                )
+            } else {
+                failure.clone()
             }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_retry() -> Result<()> {
+        for failure in failures() {
+            let (entry_point, server) = flaky_server(failure);
+            let client = Gorse::builder()
+                .entry_point(entry_point)
+                .max_retries(1)
+                .retry_backoff(Duration::from_millis(1))
+                .build()?;
+            assert_eq!(client.get_item("1").await?.item_id, "1");
+            assert_eq!(server.join().unwrap().len(), 2);
+        }
+        Ok(())
+    }
+
+    fn streamed_feedback() -> Vec<Feedback> {
+        ["1", "1060"]
+            .into_iter()
+            .map(|item_id| Feedback {
+                feedback_type: "watch".into(),
+                user_id: "2000".into(),
+                item_id: item_id.into(),
+                value: 1.0,
+                timestamp: "2022-11-20T13:55:27Z".into(),
+            })
+            .collect()
+    }
+
+    /// Serves the feedback of user 2000, as a whole and by pages of 1.
+    pub(crate) fn feedback_server(
+        requests: usize,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>, Vec<Feedback>) {
+        let feedback = streamed_feedback();
+        let records = feedback.clone();
+        let (entry_point, server) = test_server::serve(requests, move |head, _| {
+            let body = if head.starts_with("get /api/user/2000/feedback/watch ") {
+                json!(records)
+            } else if head.starts_with("get /api/feedback?cursor=&n=1 ") {
+                json!({"Cursor": "next", "Feedback": [records[0]]})
+            } else if head.starts_with("get /api/feedback?cursor=next&n=1 ") {
+                json!({"Feedback": [records[1]], "Cursor": ""})
+            } else {
+                panic!("unexpected request: {}", head);
+            };
+            test_server::json(&body.to_string())
+        });
+        (entry_point, server, feedback)
+    }
+
+    #[tokio::test]
+    async fn test_stream_feedback() -> Result<()> {
+        let (entry_point, server, feedback) = feedback_server(5);
+        let client = Gorse::new(entry_point, API_KEY);
+        assert_eq!(client.list_feedback("2000", "watch").await?, feedback);
+        let mut stream = client.stream_feedback("2000", "watch").await?;
+        let mut streamed = Vec::new();
+        while let Some(feedback) = stream.next().await? {
+            streamed.push(feedback);
+        }
+        assert_eq!(streamed, feedback);
+        let page = client.get_feedback("next", 1).await?;
+        assert_eq!(page.cursor, "");
+        assert_eq!(page.records, feedback[1..]);
+        let mut stream = client.stream_all_feedback(1);
+        let mut streamed = Vec::new();
+        while let Some(feedback) = stream.next().await? {
+            streamed.push(feedback);
+        }
+        assert_eq!(streamed, feedback);
+        assert!(stream.next().await?.is_none());
+        server.join().unwrap();
+        Ok(())
+    }
+
+    /// Serves `requests` requests probing the server version, answering
+    /// `api/version` with `version`, e.g. `404 Not Found`, and the probe
+    /// recommendation with `recommendation`.
+    pub(crate) fn version_server(
+        requests: usize,
+        version: String,
+        recommendation: &'static str,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        test_server::serve(requests, move |head, _| {
+            if head.starts_with("get /api/version ") {
+                version.clone()
+            } else if head.starts_with("get /api/recommend/gorse-rs-probe?n=1 ") {
+                assert!(head.contains("x-api-version: 2"));
+                test_server::json(recommendation)
+            } else {
+                panic!("unexpected request: {}", head);
+            }
+        })
+    }
+
+    pub(crate) fn not_found() -> String {
+        test_server::response("404 Not Found", "text/plain", "404 page not found")
+    }
+
+    #[tokio::test]
+    async fn test_probe_server_version() -> Result<()> {
+        let (entry_point, server) =
+            version_server(1, test_server::json(r#"{"Version":"v0.5.2"}"#), "");
+        let client = Gorse::new(entry_point, API_KEY);
+        let info = client.server_info().await?;
+        assert_eq!(info.version, Some(ServerVersion::new(0, 5, 2)));
+        assert!(info.has_named_recommenders());
+        assert!(info.has_scored_recommend());
+        // Probed once.
+        assert_eq!(client.server_info().await?, info);
+        server.join().unwrap();
+
+        // Without the version route, scores tell current servers apart.
+        let (entry_point, server) = version_server(2, not_found(), r#"[{"Id":"1","Score":0.5}]"#);
+        let client = Gorse::new(entry_point, API_KEY);
+        let info = client.server_info().await?;
+        assert_eq!(info.version, None);
+        assert!(info.has_named_recommenders());
+        assert!(info.has_scored_recommend());
+        assert_eq!(client.server_info().await?, info);
+        server.join().unwrap();
+
+        // Legacy servers return item ids.
+        let (entry_point, server) = version_server(2, not_found(), r#"["1"]"#);
+        let client = Gorse::builder()
+            .entry_point(entry_point)
+            .probe_server_version(true)
+            .build()?;
+        assert!(matches!(
+            client.get_recommend("bob", RecommendOptions { n: 1 }).await,
+            Err(Error::Unsupported(_))
+        ));
+        assert!(!client.server_info().await?.has_named_recommenders());
+        server.join().unwrap();
+
+        // An empty recommendation tells nothing, so the server is probed again.
+        let (entry_point, server) = version_server(4, not_found(), "[]");
+        let client = Gorse::new(entry_point, API_KEY);
+        assert!(client.server_info().await?.has_scored_recommend());
+        assert!(client.server_info().await?.has_scored_recommend());
+        assert_eq!(server.join().unwrap().len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_health_proxy_error() -> Result<()> {
+        let (entry_point, server) = test_server::serve(1, |_, _| {
+            test_server::response("503 Service Unavailable", "text/html", "<html></html>")
+        });
+        let client = Gorse::new(entry_point, API_KEY);
+        match client.health_ready().await {
+            Err(Error::Api {
+                status_code,
+                message,
+            }) => {
+                assert_eq!(status_code, StatusCode::SERVICE_UNAVAILABLE);
+                assert_eq!(message, "<html></html>");
+            }
+            r => panic!("Expected API error, got {:?}", r),
+        }
+        server.join().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_redacts_api_key() {
+        let client = Gorse::new(ENTRY_POINT, API_KEY);
+        assert!(!format!("{:?}", client).contains(API_KEY));
+    }
+
+    #[test]
+    fn test_batches() {
+        let records = [1, 2, 3, 4, 5];
+        let sizes: Vec<_> = batches(&records, Some(2)).map(<[_]>::len).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(batches(&records, None).count(), 1);
+        assert_eq!(batches::<i32>(&[], Some(2)).count(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(not(any(feature = "rustls-tls", feature = "native-tls")))]
+    async fn test_https_requires_tls() {
+        let client = Gorse::builder()
+            .entry_point("https://127.0.0.1:8087")
+            .build();
+        assert!(matches!(client, Err(Error::Config(_))));
+        assert!(matches!(
+            Gorse::try_new("https://127.0.0.1:8087", API_KEY),
+            Err(Error::Config(_))
+        ));
+        // Requests fail instead.
+        let client = Gorse::new("https://127.0.0.1:8087", API_KEY);
+        assert!(matches!(client.get_item("1").await, Err(Error::Reqwest(_))));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_legacy_recommenders() -> Result<()> {
+        let client = Gorse::builder()
+            .entry_point(ENTRY_POINT)
+            .api_key(API_KEY)
+            .server_version(ServerVersion::new(0, 4, 15))
+            .build()?;
+        let scores = client
+            .get_item_to_item("neighbors", "1", RecommendOptions { n: 3 })
+            .await?;
+        let neighbors = client.get_item_neighbors("1").await?;
+        assert_eq!(scores, neighbors[..3]);
+        let popular = client
+            .get_non_personalized("popular", RecommendOptions { n: 3 })
+            .await?;
+        assert_eq!(
+            popular,
+            client.get_popular(None, RecommendOptions { n: 3 }).await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_server_info() -> Result<()> {
+        let client = Gorse::builder()
+            .entry_point(ENTRY_POINT)
+            .api_key(API_KEY)
+            .probe_server_version(true)
+            .build()?;
+        client
+            .insert_user(&User {
+                user_id: "3000".into(),
+                labels: json!({}),
+                comment: "".into(),
+            })
+            .await?;
+        let ids = client
+            .get_recommend_ids("3000", RecommendOptions { n: 3 })
+            .await?;
+        assert_eq!(ids, vec!["315", "1432", "918"]);
+        let scores = client
+            .get_recommend("3000", RecommendOptions { n: 3 })
+            .await?;
+        assert_eq!(
+            scores.into_iter().map(|score| score.id).collect::<Vec<_>>(),
+            ids
+        );
+        let server = client.server_info().await?;
+        assert!(server.has_named_recommenders());
+        assert!(server.has_scored_recommend());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "blocking")]
+pub mod blocking {
+    use std::sync::{Arc, OnceLock};
+
+    use reqwest::blocking::{Client, Response};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    use crate::credentials::Credentials;
+    use crate::endpoint::{self, EndpointPool};
+    use crate::middleware::Middlewares;
+    use crate::observer::Observers;
+    use crate::response::{self, BodyRecord, Decode};
+    use crate::stream::{JsonArrayIter, PageIter};
+    use crate::{
+        batches, detected_server_info, is_health_check, is_server_failure, non_personalized_path,
+        not_ready_status, page_path, request_headers, with_options, CircuitBreaker, Error,
+        Fallback, FallbackCollector, FallbackOptions, FallbackRecommendation, Feedback,
+        GorseBuilder, GorseConfig, HealthStatus, Item, Method, Page, RecommendOptions,
+        Recommendation, Recommender, RequestHeaders, RequestParts, RequestTelemetry, ResponseParts,
+        Result, RowAffected, Rows, Score, ServerInfo, StatusCode, User, PROBE_USER_ID,
+    };
+
+    #[derive(Debug, Clone)]
+    pub struct Gorse {
+        pub(crate) endpoints: Arc<EndpointPool>,
+        pub(crate) credentials: Credentials,
+        pub(crate) client: Client,
+        pub(crate) circuit_breaker: Option<Arc<CircuitBreaker>>,
+        pub(crate) observers: Observers,
+        pub(crate) middlewares: Middlewares,
+        pub(crate) batch_size: Option<usize>,
+        pub(crate) server_info: Arc<OnceLock<ServerInfo>>,
+        pub(crate) probe_server_version: bool,
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        pub(crate) compression: Option<crate::compression::RequestCompression>,
+    }
+
+    impl Gorse {
+        /// Creates a client of a single endpoint. An endpoint the client cannot
+        /// connect to, e.g. HTTPS without a TLS feature, fails on each request;
+        /// use [`try_new`](Self::try_new) to reject it here.
+        pub fn new(entry_point: impl Into<String>, api_key: impl Into<String>) -> Self {
+            Self::builder()
+                .entry_point(entry_point)
+                .api_key(api_key)
+                .build_blocking_unchecked()
+                .expect("a single endpoint without TLS settings is always valid")
+        }
+
+        /// Creates a client of a single endpoint, failing with [`Error::Config`]
+        /// if the client cannot connect to it.
+        pub fn try_new(entry_point: impl Into<String>, api_key: impl Into<String>) -> Result<Self> {
+            Self::builder()
+                .entry_point(entry_point)
+                .api_key(api_key)
+                .build_blocking()
+        }
+
+        /// Creates a builder to configure a client, e.g. with several endpoints.
+        /// Finish it with [`GorseBuilder::build_blocking`].
+        pub fn builder() -> GorseBuilder {
+            GorseBuilder::new()
+        }
+
+        /// Creates a client configured by environment variables, see
+        /// [`GorseConfig::from_env`].
+        pub fn from_env() -> Result<Self> {
+            GorseConfig::from_env()?.build_blocking()
         }
 
         pub fn insert_user(&self, user: &User) -> Result<RowAffected> {
-            self.request(Method::POST, format!("{}api/user", self.entry_point), user)
+            self.request("insert_user", Method::POST, "api/user".into(), user)
         }
 
         pub fn get_user(&self, user_id: &str) -> Result<User> {
             self.request::<(), User>(
+                "get_user",
                 Method::GET,
-                format!("{}api/user/{}", self.entry_point, user_id),
+                format!("api/user/{}", user_id),
                 &(),
             )
         }
 
         pub fn delete_user(&self, user_id: &str) -> Result<RowAffected> {
             self.request::<(), RowAffected>(
+                "delete_user",
                 Method::DELETE,
-                format!("{}api/user/{}", self.entry_point, user_id),
+                format!("api/user/{}", user_id),
+                &(),
+            )
+        }
+
+        /// Inserts users, in batches of the configured
+        /// [`batch_size`](GorseBuilder::batch_size). Batches sent before a failed
+        /// batch stay inserted.
+        pub fn insert_users(&self, users: &[User]) -> Result<RowAffected> {
+            self.insert_batches("insert_users", "api/users", users)
+        }
+
+        /// Lists users by pages of `n`, starting at `cursor`, which is empty for
+        /// the first page.
+        pub fn get_users(&self, cursor: &str, n: usize) -> Result<Page<User>> {
+            self.request::<(), Page<User>>(
+                "get_users",
+                Method::GET,
+                page_path("api/users", cursor, n),
                 &(),
             )
         }
 
         pub fn insert_item(&self, item: &Item) -> Result<RowAffected> {
-            self.request(Method::POST, format!("{}api/item", self.entry_point), item)
+            self.request("insert_item", Method::POST, "api/item".into(), item)
         }
 
         pub fn get_item(&self, item_id: &str) -> Result<Item> {
             self.request::<(), Item>(
+                "get_item",
                 Method::GET,
-                format!("{}api/item/{}", self.entry_point, item_id),
+                format!("api/item/{}", item_id),
                 &(),
             )
         }
 
         pub fn delete_item(&self, item_id: &str) -> Result<RowAffected> {
             self.request::<(), RowAffected>(
+                "delete_item",
                 Method::DELETE,
-                format!("{}api/item/{}", self.entry_point, item_id),
+                format!("api/item/{}", item_id),
                 &(),
             )
         }
 
-        pub fn insert_feedback(&self, feedback: &[Feedback]) -> Result<RowAffected> {
-            self.request(
-                Method::POST,
-                format!("{}api/feedback", self.entry_point),
-                feedback,
+        /// Inserts items, in batches of the configured
+        /// [`batch_size`](GorseBuilder::batch_size). Batches sent before a failed
+        /// batch stay inserted.
+        pub fn insert_items(&self, items: &[Item]) -> Result<RowAffected> {
+            self.insert_batches("insert_items", "api/items", items)
+        }
+
+        /// Lists items by pages of `n`, starting at `cursor`, which is empty for
+        /// the first page.
+        pub fn get_items(&self, cursor: &str, n: usize) -> Result<Page<Item>> {
+            self.request::<(), Page<Item>>(
+                "get_items",
+                Method::GET,
+                page_path("api/items", cursor, n),
+                &(),
             )
         }
 
+        /// Inserts feedback, in batches of the configured
+        /// [`batch_size`](GorseBuilder::batch_size). Batches sent before a failed
+        /// batch stay inserted.
+        pub fn insert_feedback(&self, feedback: &[Feedback]) -> Result<RowAffected> {
+            self.insert_batches("insert_feedback", "api/feedback", feedback)
+        }
+
+        // Takes a `Vec` for compatibility with existing callers.
+        #[allow(clippy::ptr_arg)]
         pub fn upsert_feedback(&self, feedback: &Vec<Feedback>) -> Result<RowAffected> {
-            self.request(
-                Method::PUT,
-                format!("{}api/feedback", self.entry_point),
-                feedback,
-            )
+            let mut total = RowAffected { row_affected: 0 };
+            for batch in batches(feedback, self.batch_size) {
+                total.row_affected += self
+                    .request::<[Feedback], RowAffected>(
+                        "upsert_feedback",
+                        Method::PUT,
+                        "api/feedback".into(),
+                        batch,
+                    )?
+                    .row_affected;
+            }
+            Ok(total)
         }
 
         pub fn delete_feedback(&self, user_id: &str, item_id: &str) -> Result<RowAffected> {
             self.request::<(), RowAffected>(
+                "delete_feedback",
                 Method::DELETE,
-                format!("{}api/feedback/{}/{}", self.entry_point, user_id, item_id),
+                format!("api/feedback/{}/{}", user_id, item_id),
                 &(),
             )
         }
 
         pub fn list_feedback(&self, user_id: &str, feedback_type: &str) -> Result<Vec<Feedback>> {
             self.request::<(), Vec<Feedback>>(
+                "list_feedback",
+                Method::GET,
+                format!("api/user/{}/feedback/{}", user_id, feedback_type),
+                &(),
+            )
+        }
+
+        /// Lists the feedback of all users by pages of `n`, starting at `cursor`,
+        /// which is empty for the first page.
+        pub fn get_feedback(&self, cursor: &str, n: usize) -> Result<Page<Feedback>> {
+            self.request::<(), Page<Feedback>>(
+                "get_feedback",
                 Method::GET,
-                format!(
-                    "{}api/user/{}/feedback/{}",
-                    self.entry_point, user_id, feedback_type
-                ),
+                page_path("api/feedback", cursor, n),
                 &(),
             )
         }
 
+        /// Iterates over the feedback of a user, decoding each record as it is
+        /// read instead of buffering the whole response.
+        pub fn stream_feedback(
+            &self,
+            user_id: &str,
+            feedback_type: &str,
+        ) -> Result<JsonArrayIter<Feedback>> {
+            self.request_stream(
+                "stream_feedback",
+                format!("api/user/{}/feedback/{}", user_id, feedback_type),
+            )
+        }
+
+        /// Streams the feedback of all users, requested by pages of `n` as the
+        /// records are iterated and decoded as they are read.
+        pub fn stream_all_feedback(&self, n: usize) -> PageIter<Feedback> {
+            PageIter::new(self.clone(), "get_feedback", "api/feedback", n)
+        }
+
         pub fn get_item_neighbors(&self, item_id: &str) -> Result<Vec<Score>> {
             self.request::<(), Vec<Score>>(
+                "get_item_neighbors",
                 Method::GET,
-                format!("{}api/item/{}/neighbors", self.entry_point, item_id),
+                format!("api/item/{}/neighbors", item_id),
                 &(),
             )
         }
+
         /// Get recommendation with scores for a user.
-    /// Uses X-API-Version: 2 header to return scores.
+        /// Uses X-API-Version: 2 header to return scores. Servers before 0.5
+        /// only return item ids, see [`get_recommend_ids`](Self::get_recommend_ids),
+        /// and fail with [`Error::Unsupported`].
         pub fn get_recommend(
             &self,
             user_id: &str,
             options: RecommendOptions,
         ) -> Result<Vec<Score>> {
-            let mut url = format!("{}api/recommend/{}", self.entry_point, user_id);
-            if options.n > 0 {
-                url = format!("{}?n={}", url, options.n);
+            let server = self.negotiated_server_info()?;
+            if let Some(server) = server.filter(|server| !server.has_scored_recommend()) {
+                return Err(server.unsupported("recommendation with scores"));
+            }
+            match self.request_recommend(user_id, options, true)? {
+                Recommendation::Scored(scores) => Ok(scores),
+                Recommendation::Ids(_) => {
+                    Err(ServerInfo::legacy().unsupported("recommendation with scores"))
+                }
             }
-            self.request_with_headers::<(), Vec<Score>>(Method::GET, url, &(), Some("2"))
         }
 
-        fn request<BodyType: Serialize + ?Sized, RetType: for<'a> Deserialize<'a>>(
+        /// Get recommended item ids for a user, from servers of any version.
+        pub fn get_recommend_ids(
             &self,
+            user_id: &str,
+            options: RecommendOptions,
+        ) -> Result<Vec<String>> {
+            let scored = self
+                .negotiated_server_info()?
+                .is_none_or(|server| server.has_scored_recommend());
+            Ok(self.request_recommend(user_id, options, scored)?.into_ids())
+        }
+
+        /// Get popular items, optionally restricted to a category.
+        pub fn get_popular(
+            &self,
+            category: Option<&str>,
+            options: RecommendOptions,
+        ) -> Result<Vec<Score>> {
+            self.request::<(), Vec<Score>>(
+                "get_popular",
+                Method::GET,
+                non_personalized_path("api/popular", category, &options),
+                &(),
+            )
+        }
+
+        /// Get latest items, optionally restricted to a category.
+        pub fn get_latest(
+            &self,
+            category: Option<&str>,
+            options: RecommendOptions,
+        ) -> Result<Vec<Score>> {
+            self.request::<(), Vec<Score>>(
+                "get_latest",
+                Method::GET,
+                non_personalized_path("api/latest", category, &options),
+                &(),
+            )
+        }
+
+        /// Get items similar to an item from a named item-to-item recommender.
+        /// Servers older than 0.5 have a single recommender, used whatever the
+        /// name.
+        pub fn get_item_to_item(
+            &self,
+            name: &str,
+            item_id: &str,
+            options: RecommendOptions,
+        ) -> Result<Vec<Score>> {
+            self.recommend(Recommender::ItemToItem { name, item_id }, options)
+        }
+
+        /// Get users similar to a user from a named user-to-user recommender.
+        /// Servers older than 0.5 have a single recommender, used whatever the
+        /// name.
+        pub fn get_user_to_user(
+            &self,
+            name: &str,
+            user_id: &str,
+            options: RecommendOptions,
+        ) -> Result<Vec<Score>> {
+            self.recommend(Recommender::UserToUser { name, user_id }, options)
+        }
+
+        /// Get the collaborative filtering recommendation of a user, before it
+        /// is merged with the other recommenders.
+        pub fn get_collaborative_filtering(
+            &self,
+            user_id: &str,
+            options: RecommendOptions,
+        ) -> Result<Vec<Score>> {
+            self.recommend(Recommender::CollaborativeFiltering { user_id }, options)
+        }
+
+        /// Get items from a named non-personalized recommender, e.g. `popular`.
+        /// Servers older than 0.5 only have `popular` and `latest`.
+        pub fn get_non_personalized(
+            &self,
+            name: &str,
+            options: RecommendOptions,
+        ) -> Result<Vec<Score>> {
+            self.recommend(Recommender::NonPersonalized { name }, options)
+        }
+
+        /// Get recommendation for a user, trying the sources in
+        /// `options.strategies` in order until `options.n` items are collected.
+        /// Sources that fail are skipped and reported in the result.
+        pub fn recommend_with_fallback(
+            &self,
+            user_id: &str,
+            options: &FallbackOptions,
+        ) -> FallbackRecommendation {
+            let mut collector = FallbackCollector::new(options);
+            for strategy in &options.strategies {
+                if collector.is_full() {
+                    break;
+                }
+                let fetch = RecommendOptions {
+                    n: collector.fetch_size(),
+                };
+                let result = match strategy {
+                    Fallback::Personalized => self.get_recommend(user_id, fetch),
+                    Fallback::CategoryPopular(category) => self.get_popular(Some(category), fetch),
+                    Fallback::Popular => self.get_popular(None, fetch),
+                    Fallback::CategoryLatest(category) => self.get_latest(Some(category), fetch),
+                    Fallback::Latest => self.get_latest(None, fetch),
+                    Fallback::Static(_) => Ok(Vec::new()),
+                };
+                collector.collect(strategy, result);
+            }
+            collector.finish()
+        }
+
+        /// Returns the version and capabilities of the server. The server is
+        /// probed once, and the result is shared by the clones of the client.
+        pub fn server_info(&self) -> Result<ServerInfo> {
+            if let Some(server) = self.server_info.get() {
+                return Ok(server.clone());
+            }
+            let server = match self.request::<(), ServerInfo>(
+                "server_info",
+                Method::GET,
+                "api/version".into(),
+                &(),
+            ) {
+                Err(Error::Api {
+                    status_code: StatusCode::NOT_FOUND,
+                    ..
+                }) => match detected_server_info(self.request_recommend(
+                    PROBE_USER_ID,
+                    RecommendOptions { n: 1 },
+                    true,
+                ))? {
+                    Some(server) => server,
+                    None => return Ok(ServerInfo::unreported(true)),
+                },
+                r => r?,
+            };
+            Ok(self.server_info.get_or_init(|| server).clone())
+        }
+
+        /// Checks whether the server is alive.
+        pub fn health_live(&self) -> Result<HealthStatus> {
+            self.request::<(), HealthStatus>(
+                "health_live",
+                Method::GET,
+                "api/health/live".into(),
+                &(),
+            )
+        }
+
+        /// Checks whether the server and its data stores are ready. A server that
+        /// is not ready responds with `503 Service Unavailable`, which is returned
+        /// as a status with `ready` set to false rather than as an error. Other
+        /// `503` responses, e.g. from a proxy, are errors.
+        pub fn health_ready(&self) -> Result<HealthStatus> {
+            not_ready_status(self.request::<(), HealthStatus>(
+                "health_ready",
+                Method::GET,
+                "api/health/ready".into(),
+                &(),
+            ))
+        }
+
+        /// Server the routes and decoding of version dependent calls are chosen
+        /// for: probed if [`GorseBuilder::probe_server_version`] is set, else the
+        /// configured version, if any. `None` stands for the latest server.
+        fn negotiated_server_info(&self) -> Result<Option<ServerInfo>> {
+            if self.probe_server_version {
+                Ok(Some(self.server_info()?))
+            } else {
+                Ok(self.server_info.get().cloned())
+            }
+        }
+
+        fn recommend(
+            &self,
+            recommender: Recommender<'_>,
+            options: RecommendOptions,
+        ) -> Result<Vec<Score>> {
+            let server = self.negotiated_server_info()?;
+            self.request::<(), Vec<Score>>(
+                recommender.endpoint(),
+                Method::GET,
+                recommender.path(server.as_ref(), &options)?,
+                &(),
+            )
+        }
+
+        fn request_recommend(
+            &self,
+            user_id: &str,
+            options: RecommendOptions,
+            scored: bool,
+        ) -> Result<Recommendation> {
+            self.request_with_headers::<(), Recommendation>(
+                "get_recommend",
+                Method::GET,
+                with_options(format!("api/recommend/{}", user_id), &options),
+                &(),
+                scored.then_some("2"),
+            )
+        }
+
+        pub(crate) fn insert_batches<T: Serialize>(
+            &self,
+            endpoint: &'static str,
+            path: &str,
+            records: &[T],
+        ) -> Result<RowAffected> {
+            let mut total = RowAffected { row_affected: 0 };
+            for batch in batches(records, self.batch_size) {
+                total.row_affected += self
+                    .request::<[T], RowAffected>(endpoint, Method::POST, path.into(), batch)?
+                    .row_affected;
+            }
+            Ok(total)
+        }
+
+        pub(crate) fn request<BodyType: Serialize + Rows + ?Sized, RetType: Decode + Rows>(
+            &self,
+            endpoint: &'static str,
             method: Method,
-            url: String,
+            path: String,
             body: &BodyType,
         ) -> Result<RetType> {
-            self.request_with_headers(method, url, body, None)
+            self.request_with_headers(endpoint, method, path, body, None)
         }
 
-        fn request_with_headers<BodyType: Serialize + ?Sized, RetType: for<'a> Deserialize<'a>>(
+        fn request_with_headers<BodyType: Serialize + Rows + ?Sized, RetType: Decode + Rows>(
             &self,
+            endpoint: &'static str,
             method: Method,
-            url: String,
+            path: String,
             body: &BodyType,
             api_version: Option<&str>,
         ) -> Result<RetType> {
-            let mut request = self
-                .client
-                .request(method, url)
-                .header("X-API-Key", self.api_key.as_str())
-                .header("Content-Type", "application/json")
-                .json(body);
-            
-            if let Some(version) = api_version {
-                request = request.header("X-API-Version", version);
+            let bytes = serde_json::to_vec(body)?;
+            let body_size = bytes.len();
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            let (bytes, content_encoding) = crate::compression::compress(self.compression, bytes)?;
+            #[cfg(not(any(feature = "gzip", feature = "zstd")))]
+            let content_encoding = None;
+            let telemetry = RequestTelemetry::start(
+                endpoint,
+                &method,
+                bytes.len(),
+                body_size,
+                body.rows(),
+                &self.observers,
+            );
+            let headers = RequestHeaders {
+                api_version,
+                content_encoding,
+            };
+            let result =
+                telemetry.in_scope(|| self.execute(&telemetry, method, path, bytes, headers));
+            telemetry.finish(result.as_ref());
+            result
+        }
+
+        fn execute<RetType: Decode>(
+            &self,
+            telemetry: &RequestTelemetry<'_>,
+            method: Method,
+            path: String,
+            body: Vec<u8>,
+            headers: RequestHeaders<'_>,
+        ) -> Result<RetType> {
+            let response = self.open(telemetry, method, &path, body, headers)?;
+            let status_code = response.status();
+            let mut record = BodyRecord::default();
+            let result = response::read_body_blocking(response, &mut record);
+            telemetry.record_response(status_code, record.count);
+            result
+        }
+
+        /// Sends a request guarded by the circuit breaker and returns the
+        /// response if it is successful, leaving its body unread.
+        fn open(
+            &self,
+            telemetry: &RequestTelemetry<'_>,
+            method: Method,
+            path: &str,
+            body: Vec<u8>,
+            headers: RequestHeaders<'_>,
+        ) -> Result<Response> {
+            let mut permit = match &self.circuit_breaker {
+                Some(circuit_breaker) if !is_health_check(path) => Some(circuit_breaker.acquire()?),
+                _ => None,
+            };
+            let response = self.send(telemetry, method, path, body, headers);
+            if let Some(permit) = permit.as_mut() {
+                match &response {
+                    Ok(_) => permit.record(true),
+                    Err(err) if is_server_failure(err) => permit.record(false),
+                    // Dropped without an outcome, which leaves the breaker as
+                    // it was.
+                    Err(_) => {}
+                }
             }
-            
-            let response = request.send()?;
-            if response.status() == StatusCode::OK {
-                let r: RetType = serde_json::from_str(response.text()?.as_str())?;
-                Ok(r)
-            } else {
-                Err(Error::Api {
-                    status_code: response.status(),
-                    message: response.text()?,
-                })
+            let response = response?;
+            let status_code = response.status();
+            if status_code.is_success() {
+                return Ok(response);
+            }
+            let text = response.text()?;
+            telemetry.record_response(status_code, text.len());
+            Err(Error::Api {
+                status_code,
+                message: text,
+            })
+        }
+
+        /// Sends a GET request and iterates over the elements of the JSON
+        /// array it returns. Observers are notified once the response headers
+        /// are received.
+        fn request_stream<RetType: DeserializeOwned>(
+            &self,
+            endpoint: &'static str,
+            path: String,
+        ) -> Result<JsonArrayIter<RetType>> {
+            Ok(JsonArrayIter::new(self.open_stream(endpoint, path)?))
+        }
+
+        /// Sends a GET request and returns the response of a JSON body,
+        /// leaving it unread.
+        pub(crate) fn open_stream(&self, endpoint: &'static str, path: String) -> Result<Response> {
+            let method = Method::GET;
+            let telemetry = RequestTelemetry::start(endpoint, &method, 0, 0, None, &self.observers);
+            let result = telemetry.in_scope(|| {
+                self.open(
+                    &telemetry,
+                    method,
+                    &path,
+                    Vec::new(),
+                    RequestHeaders::default(),
+                )
+            });
+            telemetry.finish(result.as_ref().map(|_| &()));
+            let response = result?;
+            response::check_content_type(response.status(), response.headers(), &[])?;
+            Ok(response)
+        }
+
+        /// Sends a request to the endpoints in the order chosen by the load
+        /// balancing strategy. Reads fail over to the next endpoint on connection
+        /// errors and server errors, and are retried after a backoff once every
+        /// endpoint failed; writes are only attempted once. Health checks
+        /// return server errors, e.g. `503` from a server that is not ready, and
+        /// leave the health of endpoints unchanged.
+        fn send(
+            &self,
+            telemetry: &RequestTelemetry<'_>,
+            method: Method,
+            path: &str,
+            body: Vec<u8>,
+            headers: RequestHeaders<'_>,
+        ) -> Result<Response> {
+            let failover = method == Method::GET || method == Method::HEAD;
+            let health_check = is_health_check(path);
+            let headers = request_headers(&self.credentials, headers)?;
+            self.spawn_probes();
+            let retry = self.endpoints.retry();
+            let rounds = if failover { retry.max_retries } else { 0 };
+            let mut last_error = None;
+            for round in 0..=rounds {
+                if round > 0 {
+                    std::thread::sleep(retry.delay(round));
+                }
+                for index in self.endpoints.candidates() {
+                    if let Some(error) = &last_error {
+                        telemetry.record_retry(error);
+                    }
+                    let _outstanding = self.endpoints.begin(index);
+                    let mut request = RequestParts {
+                        method: method.clone(),
+                        url: format!("{}{}", self.endpoints.url(index), path),
+                        headers: headers.clone(),
+                        body: body.clone(),
+                    };
+                    self.middlewares.on_request(&mut request)?;
+
+                    let result = self
+                        .client
+                        .request(request.method.clone(), request.url.as_str())
+                        .headers(request.headers)
+                        .body(request.body)
+                        .send();
+                    let error = match result {
+                        Ok(response) => {
+                            self.middlewares.on_response(&ResponseParts {
+                                method: &request.method,
+                                url: &request.url,
+                                status: response.status(),
+                                headers: response.headers(),
+                            });
+                            if health_check {
+                                return Ok(response);
+                            }
+                            if !response.status().is_server_error() {
+                                self.endpoints.mark_success(index);
+                                return Ok(response);
+                            }
+                            self.endpoints.mark_failure(index);
+                            Error::Api {
+                                status_code: response.status(),
+                                // A body that cannot be read must not stop
+                                // the failover.
+                                message: response.text().unwrap_or_default(),
+                            }
+                        }
+                        Err(err) => {
+                            let err = err.into();
+                            self.middlewares
+                                .on_error(&request.method, &request.url, &err);
+                            if !health_check {
+                                self.endpoints.mark_failure(index);
+                            }
+                            err
+                        }
+                    };
+                    last_error = Some(error);
+                    if !failover {
+                        break;
+                    }
+                }
+            }
+            Err(last_error.expect("endpoint pool is never empty"))
+        }
+
+        /// Probes unhealthy endpoints that are due in a background thread,
+        /// which brings them back on success, so that requests do not wait for
+        /// probes.
+        fn spawn_probes(&self) {
+            let due = self.endpoints.due_for_probe();
+            if due.is_empty() {
+                return;
             }
+            let client = self.clone();
+            std::thread::spawn(move || {
+                for index in due {
+                    let probe = client.probe(index);
+                    if matches!(probe, Ok(response) if response.status().is_success()) {
+                        client.endpoints.mark_success(index);
+                    }
+                }
+            });
+        }
+
+        fn probe(&self, index: usize) -> Result<Response> {
+            let mut request = RequestParts {
+                method: Method::GET,
+                url: format!("{}api/health/live", self.endpoints.url(index)),
+                headers: request_headers(&self.credentials, RequestHeaders::default())?,
+                body: Vec::new(),
+            };
+            self.middlewares.on_request(&mut request)?;
+            Ok(self
+                .client
+                .request(request.method, request.url)
+                .headers(request.headers)
+                .timeout(endpoint::PROBE_TIMEOUT)
+                .send()?)
         }
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
+        use crate::{test_server, Middleware, ServerVersion};
         use serde_json::json;
         use serial_test::serial;
 
@@ -714,5 +2576,266 @@ pub mod blocking {
             assert_eq!(items[2].id, "918");
             Ok(())
         }
+
+        #[test]
+        #[serial]
+        fn test_failover() -> Result<()> {
+            let client = Gorse::builder()
+                .endpoints(["http://127.0.0.1:1/", ENTRY_POINT])
+                .api_key(API_KEY)
+                .failure_threshold(1)
+                .build_blocking()?;
+            for _ in 0..3 {
+                let scores = client.get_item_neighbors("1")?;
+                assert_eq!(scores[0].id, "1060".to_string());
+            }
+            Ok(())
+        }
+
+        #[test]
+        #[serial]
+        fn test_health() -> Result<()> {
+            let client = Gorse::new(ENTRY_POINT, API_KEY);
+            let status = client.health_live()?;
+            assert!(status.ready);
+            let status = client.health_ready()?;
+            assert!(status.ready);
+            assert!(status.data_store_connected);
+            assert!(status.cache_store_connected);
+            Ok(())
+        }
+
+        #[test]
+        #[serial]
+        fn test_circuit_breaker() -> Result<()> {
+            let client = Gorse::builder()
+                .entry_point("http://127.0.0.1:1/")
+                .circuit_breaker(CircuitBreaker::new().minimum_calls(1))
+                .build_blocking()?;
+            assert!(matches!(
+                client.get_item_neighbors("1"),
+                Err(Error::Reqwest(_))
+            ));
+            assert!(matches!(
+                client.get_item_neighbors("1"),
+                Err(Error::CircuitOpen)
+            ));
+            Ok(())
+        }
+
+        #[test]
+        fn test_circuit_breaker_ignores_local_errors() -> Result<()> {
+            let client = Gorse::builder()
+                .entry_point("http://127.0.0.1:1/")
+                .credentials(crate::FileCredentials::new("/nonexistent/gorse_api_key"))
+                .circuit_breaker(CircuitBreaker::new().minimum_calls(1))
+                .build_blocking()?;
+            for _ in 0..3 {
+                assert!(matches!(client.get_item("1"), Err(Error::Config(_))));
+            }
+            Ok(())
+        }
+
+        #[test]
+        #[serial]
+        fn test_fallback() -> Result<()> {
+            let client = Gorse::new(ENTRY_POINT, API_KEY);
+            let popular = client.get_popular(None, RecommendOptions { n: 3 })?;
+            assert_eq!(popular.len(), 3);
+            let latest = client.get_latest(None, RecommendOptions { n: 3 })?;
+            assert_eq!(latest.len(), 3);
+
+            let options = FallbackOptions {
+                n: 6,
+                strategies: vec![
+                    Fallback::Personalized,
+                    Fallback::Popular,
+                    Fallback::Static(vec!["1".into()]),
+                ],
+                exclude: vec![popular[0].id.clone()],
+            };
+            let recommendation = client.recommend_with_fallback("not-exist", &options);
+            assert_eq!(recommendation.items.len(), 6);
+            assert!(recommendation
+                .items
+                .iter()
+                .all(|item| item.id != popular[0].id));
+            Ok(())
+        }
+
+        struct Rewrite;
+
+        impl Middleware for Rewrite {
+            fn on_request(&self, request: &mut RequestParts) -> Result<()> {
+                if request.url.contains("/denied/") {
+                    return Err(Error::Middleware("denied".into()));
+                }
+                request.url = request.url.replace("http://127.0.0.1:1/", ENTRY_POINT);
+                Ok(())
+            }
+        }
+
+        #[test]
+        #[serial]
+        fn test_middleware() -> Result<()> {
+            let client = Gorse::builder()
+                .entry_point("http://127.0.0.1:1/")
+                .api_key(API_KEY)
+                .middleware(Rewrite)
+                .build_blocking()?;
+            assert!(matches!(
+                client.get_item_neighbors("denied"),
+                Err(Error::Middleware(_))
+            ));
+            let scores = client.get_item_neighbors("1")?;
+            assert_eq!(scores[0].id, "1060".to_string());
+            Ok(())
+        }
+
+        #[test]
+        fn test_health_not_ready() -> Result<()> {
+            let (entry_point, server) = test_server::serve(2, |_, _| {
+                test_server::response(
+                    "503 Service Unavailable",
+                    "application/json",
+                    r#"{"Ready":false,"DataStoreConnected":false,"CacheStoreConnected":true}"#,
+                )
+            });
+            let client = Gorse::builder()
+                .entry_point(entry_point)
+                .failure_threshold(1)
+                .probe_interval(std::time::Duration::ZERO)
+                .circuit_breaker(CircuitBreaker::new().minimum_calls(1))
+                .build_blocking()?;
+            assert!(!client.health_ready()?.ready);
+            assert!(!client.health_ready()?.ready);
+            assert!(client.endpoints.due_for_probe().is_empty());
+            server.join().unwrap();
+            Ok(())
+        }
+
+        #[test]
+        fn test_stream_feedback() -> Result<()> {
+            let (entry_point, server, feedback) = crate::tests::feedback_server(4);
+            let client = Gorse::new(entry_point, API_KEY);
+            let streamed = client
+                .stream_feedback("2000", "watch")?
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(streamed, feedback);
+            let page = client.get_feedback("", 1)?;
+            assert_eq!(page.cursor, "next");
+            assert_eq!(page.records, feedback[..1]);
+            let streamed = client.stream_all_feedback(1).collect::<Result<Vec<_>>>()?;
+            assert_eq!(streamed, feedback);
+            server.join().unwrap();
+            Ok(())
+        }
+
+        #[test]
+        fn test_probe_server_version() -> Result<()> {
+            let (entry_point, server) =
+                crate::tests::version_server(1, test_server::json(r#"{"Version":"v0.4.15"}"#), "");
+            let client = Gorse::new(entry_point, API_KEY);
+            let info = client.server_info()?;
+            assert_eq!(info.version, Some(ServerVersion::new(0, 4, 15)));
+            assert!(!info.has_named_recommenders());
+            assert_eq!(client.server_info()?, info);
+            server.join().unwrap();
+
+            let (entry_point, server) = crate::tests::version_server(
+                2,
+                crate::tests::not_found(),
+                r#"[{"Id":"1","Score":0.5}]"#,
+            );
+            let client = Gorse::new(entry_point, API_KEY);
+            let info = client.server_info()?;
+            assert_eq!(info.version, None);
+            assert!(info.has_scored_recommend());
+            server.join().unwrap();
+
+            let (entry_point, server) =
+                crate::tests::version_server(2, crate::tests::not_found(), r#"["1"]"#);
+            let client = Gorse::new(entry_point, API_KEY);
+            assert!(!client.server_info()?.has_scored_recommend());
+            server.join().unwrap();
+            Ok(())
+        }
+
+        #[test]
+        fn test_retry() -> Result<()> {
+            for failure in crate::tests::failures() {
+                let (entry_point, server) = crate::tests::flaky_server(failure);
+                let client = Gorse::builder()
+                    .entry_point(entry_point)
+                    .max_retries(1)
+                    .retry_backoff(std::time::Duration::from_millis(1))
+                    .build_blocking()?;
+                assert_eq!(client.get_item("1")?.item_id, "1");
+                assert_eq!(server.join().unwrap().len(), 2);
+            }
+            Ok(())
+        }
+
+        #[test]
+        #[cfg(not(any(feature = "rustls-tls", feature = "native-tls")))]
+        fn test_https_requires_tls() {
+            assert!(matches!(
+                Gorse::try_new("https://127.0.0.1:8087", API_KEY),
+                Err(Error::Config(_))
+            ));
+            let client = Gorse::new("https://127.0.0.1:8087", API_KEY);
+            assert!(matches!(client.get_item("1"), Err(Error::Reqwest(_))));
+        }
+
+        #[test]
+        fn test_debug_redacts_api_key() {
+            let client = Gorse::new(ENTRY_POINT, API_KEY);
+            assert!(!format!("{:?}", client).contains(API_KEY));
+        }
+
+        #[test]
+        #[serial]
+        fn test_legacy_recommenders() -> Result<()> {
+            let client = Gorse::builder()
+                .entry_point(ENTRY_POINT)
+                .api_key(API_KEY)
+                .server_version(ServerVersion::new(0, 4, 15))
+                .build_blocking()?;
+            let scores = client.get_item_to_item("neighbors", "1", RecommendOptions { n: 3 })?;
+            let neighbors = client.get_item_neighbors("1")?;
+            assert_eq!(scores, neighbors[..3]);
+            let popular = client.get_non_personalized("popular", RecommendOptions { n: 3 })?;
+            assert_eq!(
+                popular,
+                client.get_popular(None, RecommendOptions { n: 3 })?
+            );
+            Ok(())
+        }
+
+        #[test]
+        #[serial]
+        fn test_server_info() -> Result<()> {
+            let client = Gorse::builder()
+                .entry_point(ENTRY_POINT)
+                .api_key(API_KEY)
+                .probe_server_version(true)
+                .build_blocking()?;
+            client.insert_user(&User {
+                user_id: "3000".into(),
+                labels: json!({}),
+                comment: "".into(),
+            })?;
+            let ids = client.get_recommend_ids("3000", RecommendOptions { n: 3 })?;
+            assert_eq!(ids, vec!["315", "1432", "918"]);
+            let scores = client.get_recommend("3000", RecommendOptions { n: 3 })?;
+            assert_eq!(
+                scores.into_iter().map(|score| score.id).collect::<Vec<_>>(),
+                ids
+            );
+            let server = client.server_info()?;
+            assert!(server.has_named_recommenders());
+            assert!(server.has_scored_recommend());
+            Ok(())
+        }
     }
 }
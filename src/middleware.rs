@@ -0,0 +1,112 @@
+use std::fmt;
+use std::sync::Arc;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+
+use crate::{Error, Result};
+
+/// An outgoing request, which middleware can inspect and modify before it is sent.
+#[derive(Debug, Clone)]
+pub struct RequestParts {
+    pub method: Method,
+    /// Full URL, including the endpoint chosen by the load balancing strategy.
+    pub url: String,
+    /// Headers, including `X-API-Key`. The key is marked sensitive so that it
+    /// is redacted when the headers are debug printed.
+    pub headers: HeaderMap,
+    /// JSON encoded body, compressed if the `Content-Encoding` header is set.
+    pub body: Vec<u8>,
+}
+
+/// A response received for a request, passed to [`Middleware::on_response`].
+#[derive(Debug)]
+pub struct ResponseParts<'a> {
+    pub method: &'a Method,
+    pub url: &'a str,
+    pub status: StatusCode,
+    pub headers: &'a HeaderMap,
+}
+
+/// Hook into the requests sent by a client, e.g. to add tenant headers, sign
+/// requests or route them through a proxy.
+///
+/// Middleware runs for every attempt, so a read that fails over runs it once
+/// per endpoint tried. Requests go through middleware in registration order
+/// and responses and errors in reverse order.
+///
+/// ```
+/// use gorse_rs::{Error, Gorse, Middleware, RequestParts};
+///
+/// struct Tenant(&'static str);
+///
+/// impl Middleware for Tenant {
+///     fn on_request(&self, request: &mut RequestParts) -> Result<(), Error> {
+///         request.headers.insert("X-Tenant", self.0.parse().unwrap());
+///         Ok(())
+///     }
+/// }
+///
+/// let client = Gorse::builder()
+///     .entry_point("http://127.0.0.1:8087")
+///     .middleware(Tenant("shop"))
+///     .build()?;
+/// # Ok::<(), gorse_rs::Error>(())
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Called before a request is sent. Returning an error aborts the call
+    /// with that error.
+    fn on_request(&self, _request: &mut RequestParts) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when response headers are received.
+    fn on_response(&self, _response: &ResponseParts<'_>) {}
+
+    /// Called when a request could not be sent or no response was received.
+    fn on_error(&self, _method: &Method, _url: &str, _error: &Error) {}
+}
+
+impl<M: Middleware + ?Sized> Middleware for Arc<M> {
+    fn on_request(&self, request: &mut RequestParts) -> Result<()> {
+        (**self).on_request(request)
+    }
+
+    fn on_response(&self, response: &ResponseParts<'_>) {
+        (**self).on_response(response)
+    }
+
+    fn on_error(&self, method: &Method, url: &str, error: &Error) {
+        (**self).on_error(method, url, error)
+    }
+}
+
+/// Middleware chain registered on a client.
+#[derive(Clone, Default)]
+pub(crate) struct Middlewares(pub(crate) Vec<Arc<dyn Middleware>>);
+
+impl Middlewares {
+    pub(crate) fn on_request(&self, request: &mut RequestParts) -> Result<()> {
+        self.0
+            .iter()
+            .try_for_each(|middleware| middleware.on_request(request))
+    }
+
+    pub(crate) fn on_response(&self, response: &ResponseParts<'_>) {
+        for middleware in self.0.iter().rev() {
+            middleware.on_response(response);
+        }
+    }
+
+    pub(crate) fn on_error(&self, method: &Method, url: &str, error: &Error) {
+        for middleware in self.0.iter().rev() {
+            middleware.on_error(method, url, error);
+        }
+    }
+}
+
+impl fmt::Debug for Middlewares {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Middlewares({})", self.0.len())
+    }
+}
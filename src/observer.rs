@@ -0,0 +1,146 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::{Method, StatusCode};
+
+use crate::Error;
+
+/// A call to the Gorse API, passed to [`Observer`] callbacks.
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    /// Name of the client method, e.g. `insert_feedback`.
+    pub endpoint: &'static str,
+    pub method: Method,
+    /// Size of the request body in bytes, as sent.
+    pub bytes_sent: usize,
+    /// Size of the request body in bytes before compression, equal to
+    /// `bytes_sent` unless the body was compressed.
+    pub body_size: usize,
+    /// Number of records in the request body for batch endpoints.
+    pub batch_size: Option<usize>,
+}
+
+/// The outcome of a call to the Gorse API, passed to [`Observer::on_request_finish`].
+#[derive(Debug)]
+pub struct RequestOutcome<'a> {
+    /// Status code of the last response, if any was received.
+    pub status_code: Option<StatusCode>,
+    /// Size of the response body in bytes, after decompression.
+    pub bytes_received: usize,
+    /// Number of rows affected or returned.
+    pub rows: Option<usize>,
+    /// Number of endpoints tried, greater than 1 when reads failed over.
+    pub attempts: usize,
+    pub duration: Duration,
+    pub error: Option<&'a Error>,
+}
+
+/// Callbacks invoked by the clients around each call, e.g. to export metrics.
+///
+/// ```
+/// use gorse_rs::{Gorse, Observer, RequestInfo, RequestOutcome};
+///
+/// struct Logger;
+///
+/// impl Observer for Logger {
+///     fn on_request_finish(&self, request: &RequestInfo, outcome: &RequestOutcome<'_>) {
+///         eprintln!("{} took {:?}", request.endpoint, outcome.duration);
+///     }
+/// }
+///
+/// let client = Gorse::builder()
+///     .entry_point("http://127.0.0.1:8087")
+///     .observer(Logger)
+///     .build()?;
+/// # Ok::<(), gorse_rs::Error>(())
+/// ```
+pub trait Observer: Send + Sync {
+    /// Called before the request is sent.
+    fn on_request_start(&self, _request: &RequestInfo) {}
+
+    /// Called when a read fails on an endpoint and is retried on the next one.
+    fn on_retry(&self, _request: &RequestInfo, _error: &Error) {}
+
+    /// Called once the call completed, successfully or not.
+    fn on_request_finish(&self, _request: &RequestInfo, _outcome: &RequestOutcome<'_>) {}
+}
+
+impl<O: Observer + ?Sized> Observer for Arc<O> {
+    fn on_request_start(&self, request: &RequestInfo) {
+        (**self).on_request_start(request)
+    }
+
+    fn on_retry(&self, request: &RequestInfo, error: &Error) {
+        (**self).on_retry(request, error)
+    }
+
+    fn on_request_finish(&self, request: &RequestInfo, outcome: &RequestOutcome<'_>) {
+        (**self).on_request_finish(request, outcome)
+    }
+}
+
+/// Observers registered on a client.
+#[derive(Clone, Default)]
+pub(crate) struct Observers(pub(crate) Vec<Arc<dyn Observer>>);
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+/// [`Observer`] recording Prometheus style metrics through the `metrics` facade:
+///
+/// - `gorse_requests_total` counter by `endpoint`, `method` and `status`
+///   (`2xx`, `3xx`, `4xx`, `5xx`, `error` or `circuit_open`),
+/// - `gorse_request_duration_seconds` histogram by `endpoint` and `method`,
+/// - `gorse_request_retries_total` counter by `endpoint`,
+/// - `gorse_bytes_sent_total` and `gorse_bytes_received_total` counters by `endpoint`,
+/// - `gorse_batch_size` histogram by `endpoint` for batch endpoints.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsObserver;
+
+#[cfg(feature = "metrics")]
+impl Observer for MetricsObserver {
+    fn on_request_start(&self, request: &RequestInfo) {
+        metrics::counter!("gorse_bytes_sent_total", "endpoint" => request.endpoint)
+            .increment(request.bytes_sent as u64);
+        if let Some(batch_size) = request.batch_size {
+            metrics::histogram!("gorse_batch_size", "endpoint" => request.endpoint)
+                .record(batch_size as f64);
+        }
+    }
+
+    fn on_retry(&self, request: &RequestInfo, _error: &Error) {
+        metrics::counter!("gorse_request_retries_total", "endpoint" => request.endpoint)
+            .increment(1);
+    }
+
+    fn on_request_finish(&self, request: &RequestInfo, outcome: &RequestOutcome<'_>) {
+        let status = match (outcome.error, outcome.status_code) {
+            (Some(Error::CircuitOpen), _) => "circuit_open",
+            (_, Some(status_code)) if status_code.is_success() => "2xx",
+            (_, Some(status_code)) if status_code.is_redirection() => "3xx",
+            (_, Some(status_code)) if status_code.is_client_error() => "4xx",
+            (_, Some(status_code)) if status_code.is_server_error() => "5xx",
+            _ => "error",
+        };
+        metrics::counter!(
+            "gorse_requests_total",
+            "endpoint" => request.endpoint,
+            "method" => request.method.to_string(),
+            "status" => status
+        )
+        .increment(1);
+        metrics::histogram!(
+            "gorse_request_duration_seconds",
+            "endpoint" => request.endpoint,
+            "method" => request.method.to_string()
+        )
+        .record(outcome.duration.as_secs_f64());
+        metrics::counter!("gorse_bytes_received_total", "endpoint" => request.endpoint)
+            .increment(outcome.bytes_received as u64);
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Deserialize;
+
+use crate::response::Decode;
+use crate::telemetry::Rows;
+use crate::{with_options, RecommendOptions, Result, Score, ServerInfo};
+
+/// Recommender configured on the server, served by a named route since
+/// Gorse 0.5 and by a legacy route before.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Recommender<'a> {
+    ItemToItem { name: &'a str, item_id: &'a str },
+    UserToUser { name: &'a str, user_id: &'a str },
+    CollaborativeFiltering { user_id: &'a str },
+    NonPersonalized { name: &'a str },
+}
+
+impl Recommender<'_> {
+    pub(crate) fn endpoint(&self) -> &'static str {
+        match self {
+            Recommender::ItemToItem { .. } => "get_item_to_item",
+            Recommender::UserToUser { .. } => "get_user_to_user",
+            Recommender::CollaborativeFiltering { .. } => "get_collaborative_filtering",
+            Recommender::NonPersonalized { .. } => "get_non_personalized",
+        }
+    }
+
+    /// Path of the recommender on a server, or on the latest server if
+    /// unknown. Legacy servers have a single item and user neighbors
+    /// recommender, used whatever the name, and only the `popular` and
+    /// `latest` non-personalized recommenders.
+    pub(crate) fn path(
+        &self,
+        server: Option<&ServerInfo>,
+        options: &RecommendOptions,
+    ) -> Result<String> {
+        let named = server.is_none_or(ServerInfo::has_named_recommenders);
+        let path = match (*self, named) {
+            (Recommender::ItemToItem { name, item_id }, true) => {
+                format!("api/item-to-item/{}/{}", name, item_id)
+            }
+            (Recommender::ItemToItem { item_id, .. }, false) => {
+                format!("api/item/{}/neighbors", item_id)
+            }
+            (Recommender::UserToUser { name, user_id }, true) => {
+                format!("api/user-to-user/{}/{}", name, user_id)
+            }
+            (Recommender::UserToUser { user_id, .. }, false) => {
+                format!("api/user/{}/neighbors", user_id)
+            }
+            (Recommender::CollaborativeFiltering { user_id }, true) => {
+                format!("api/collaborative-filtering/{}", user_id)
+            }
+            (Recommender::CollaborativeFiltering { user_id }, false) => {
+                format!("api/intermediate/recommend/{}", user_id)
+            }
+            (Recommender::NonPersonalized { name }, true) => {
+                format!("api/non-personalized/{}", name)
+            }
+            (Recommender::NonPersonalized { name: "popular" }, false) => "api/popular".into(),
+            (Recommender::NonPersonalized { name: "latest" }, false) => "api/latest".into(),
+            (Recommender::NonPersonalized { name }, false) => {
+                let server = server.expect("legacy routes are only used for known servers");
+                return Err(
+                    server.unsupported(format_args!("non-personalized recommender {:?}", name))
+                );
+            }
+        };
+        Ok(with_options(path, options))
+    }
+}
+
+/// Recommendation of a user: scored items from servers since 0.5, or item
+/// ids only from older servers.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Recommendation {
+    Scored(Vec<Score>),
+    Ids(Vec<String>),
+}
+
+impl Recommendation {
+    pub(crate) fn into_ids(self) -> Vec<String> {
+        match self {
+            Recommendation::Scored(scores) => scores.into_iter().map(|score| score.id).collect(),
+            Recommendation::Ids(ids) => ids,
+        }
+    }
+}
+
+impl Recommendation {
+    /// Server returning the recommendation, if it does not report its
+    /// version: servers before 0.5 return item ids, later ones scores.
+    /// `None` for an empty recommendation, which does not tell them apart.
+    pub(crate) fn server_info(&self) -> Option<ServerInfo> {
+        match self {
+            Recommendation::Scored(scores) if scores.is_empty() => None,
+            Recommendation::Scored(_) => Some(ServerInfo::unreported(true)),
+            Recommendation::Ids(_) => Some(ServerInfo::legacy()),
+        }
+    }
+}
+
+impl Decode for Recommendation {
+    fn empty() -> Option<Self> {
+        Some(Recommendation::Scored(Vec::new()))
+    }
+}
+
+impl Rows for Recommendation {
+    fn rows(&self) -> Option<usize> {
+        match self {
+            Recommendation::Scored(scores) => Some(scores.len()),
+            Recommendation::Ids(ids) => Some(ids.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, ServerVersion};
+
+    #[test]
+    fn test_path() -> Result<()> {
+        let options = RecommendOptions { n: 3 };
+        let legacy = ServerInfo::new(ServerVersion::new(0, 4, 15));
+        let item_to_item = Recommender::ItemToItem {
+            name: "similar",
+            item_id: "1",
+        };
+        assert_eq!(
+            item_to_item.path(None, &options)?,
+            "api/item-to-item/similar/1?n=3"
+        );
+        assert_eq!(
+            item_to_item.path(Some(&legacy), &options)?,
+            "api/item/1/neighbors?n=3"
+        );
+        let collaborative = Recommender::CollaborativeFiltering { user_id: "bob" };
+        assert_eq!(
+            collaborative.path(Some(&legacy), &RecommendOptions::default())?,
+            "api/intermediate/recommend/bob"
+        );
+        let popular = Recommender::NonPersonalized { name: "popular" };
+        assert_eq!(
+            popular.path(None, &options)?,
+            "api/non-personalized/popular?n=3"
+        );
+        assert_eq!(popular.path(Some(&legacy), &options)?, "api/popular?n=3");
+        let trending = Recommender::NonPersonalized { name: "trending" };
+        assert!(matches!(
+            trending.path(Some(&ServerInfo::legacy()), &options),
+            Err(Error::Unsupported(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_recommendation() -> Result<()> {
+        let scored: Recommendation = serde_json::from_str(r#"[{"Id":"1","Score":0.5}]"#)?;
+        assert!(matches!(scored, Recommendation::Scored(_)));
+        assert_eq!(scored.server_info(), Some(ServerInfo::unreported(true)));
+        assert_eq!(scored.into_ids(), vec!["1"]);
+        let ids: Recommendation = serde_json::from_str(r#"["1","2"]"#)?;
+        assert_eq!(ids.server_info(), Some(ServerInfo::legacy()));
+        assert_eq!(ids.into_ids(), vec!["1", "2"]);
+        let empty: Recommendation = serde_json::from_str("[]")?;
+        assert_eq!(empty.server_info(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::stream::{ArrayDecoder, BodyDecoder, BufferedDecoder, PageDecoder};
+use crate::{Error, Feedback, HealthStatus, Item, Page, Result, RowAffected, ServerInfo, User};
+
+/// Maximum number of bytes of a response body quoted in decode errors.
+const SNIPPET_LEN: usize = 256;
+
+/// Return type of a call to the Gorse API.
+pub(crate) trait Decode: DeserializeOwned + 'static {
+    /// Value returned for an empty response body, e.g. `204 No Content`.
+    fn empty() -> Option<Self> {
+        None
+    }
+
+    /// Decoder of the response body as it arrives, by default buffering it.
+    fn decoder() -> Box<dyn BodyDecoder<Self>> {
+        Box::new(BufferedDecoder::default())
+    }
+}
+
+impl Decode for () {
+    fn empty() -> Option<Self> {
+        Some(())
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> Decode for Vec<T> {
+    fn empty() -> Option<Self> {
+        Some(Vec::new())
+    }
+
+    fn decoder() -> Box<dyn BodyDecoder<Self>> {
+        Box::new(ArrayDecoder::default())
+    }
+}
+
+impl<K, V> Decode for HashMap<K, V>
+where
+    K: DeserializeOwned + Eq + Hash + 'static,
+    V: DeserializeOwned + 'static,
+{
+    fn empty() -> Option<Self> {
+        Some(HashMap::new())
+    }
+}
+
+impl Decode for Value {}
+
+impl Decode for RowAffected {
+    fn empty() -> Option<Self> {
+        Some(RowAffected { row_affected: 0 })
+    }
+}
+
+impl Decode for User {}
+impl Decode for Item {}
+impl Decode for Feedback {}
+impl Decode for HealthStatus {}
+impl<T: DeserializeOwned + Send + 'static> Decode for Page<T> {
+    fn decoder() -> Box<dyn BodyDecoder<Self>> {
+        Box::new(PageDecoder::default())
+    }
+}
+impl Decode for ServerInfo {}
+
+/// Decodes the body of a successful response as it arrives, recording it.
+pub(crate) async fn read_body<T: Decode>(
+    mut response: reqwest::Response,
+    record: &mut BodyRecord,
+) -> Result<T> {
+    let status_code = response.status();
+    let headers = response.headers().clone();
+    let mut decoder = T::decoder();
+    let mut fed = Ok(());
+    while let Some(chunk) = response.chunk().await? {
+        record.record(&chunk);
+        fed = decoder.feed(&chunk);
+        if fed.is_err() {
+            break;
+        }
+    }
+    record.decode(status_code, &headers, fed.and_then(|()| decoder.finish()))
+}
+
+/// Decodes the body of a successful response as it is read, recording it.
+#[cfg(feature = "blocking")]
+pub(crate) fn read_body_blocking<T: Decode>(
+    response: reqwest::blocking::Response,
+    record: &mut BodyRecord,
+) -> Result<T> {
+    let status_code = response.status();
+    let headers = response.headers().clone();
+    let result = serde_json::from_reader(std::io::BufReader::new(RecordingReader {
+        inner: response,
+        record: &mut *record,
+    }));
+    record.decode(status_code, &headers, result)
+}
+
+/// Size and first bytes of a response body, recorded as it is read.
+#[derive(Debug, Default)]
+pub(crate) struct BodyRecord {
+    pub(crate) count: usize,
+    prefix: Vec<u8>,
+}
+
+impl BodyRecord {
+    pub(crate) fn record(&mut self, bytes: &[u8]) {
+        self.count += bytes.len();
+        let keep = bytes
+            .len()
+            .min((SNIPPET_LEN + 1).saturating_sub(self.prefix.len()));
+        self.prefix.extend_from_slice(&bytes[..keep]);
+    }
+
+    /// Returns the result of decoding a successful response body, or the
+    /// value of an empty body, with decode errors quoting the body.
+    pub(crate) fn decode<T: Decode, E: ToString>(
+        &self,
+        status_code: StatusCode,
+        headers: &HeaderMap,
+        result: std::result::Result<T, E>,
+    ) -> Result<T> {
+        if self.count == self.prefix.len() && is_blank(&self.prefix) {
+            return empty(status_code, &self.prefix);
+        }
+        check_content_type(status_code, headers, &self.prefix)?;
+        result.map_err(|err| decode_error(status_code, err, &self.prefix))
+    }
+}
+
+fn empty<T: Decode>(status_code: StatusCode, body: &[u8]) -> Result<T> {
+    T::empty().ok_or_else(|| decode_error(status_code, "empty response body", body))
+}
+
+/// Checks that a response is JSON. Responses without content type are
+/// assumed to be JSON.
+pub(crate) fn check_content_type(
+    status_code: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<()> {
+    let Some(content_type) = headers.get(CONTENT_TYPE) else {
+        return Ok(());
+    };
+    let content_type = content_type.to_str().unwrap_or_default();
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if mime == "application/json" || mime.ends_with("+json") {
+        Ok(())
+    } else {
+        Err(decode_error(
+            status_code,
+            format!("unexpected content type {}", content_type),
+            body,
+        ))
+    }
+}
+
+fn is_blank(body: &[u8]) -> bool {
+    body.iter().all(u8::is_ascii_whitespace)
+}
+
+pub(crate) fn decode_error(status_code: StatusCode, reason: impl ToString, body: &[u8]) -> Error {
+    let mut snippet = String::from_utf8_lossy(&body[..body.len().min(SNIPPET_LEN)]).into_owned();
+    if body.len() > SNIPPET_LEN {
+        snippet.push_str("...");
+    }
+    Error::Decode {
+        status_code,
+        reason: reason.to_string(),
+        body: snippet,
+    }
+}
+
+/// Reader recording the body read through it for decode errors.
+#[cfg(feature = "blocking")]
+struct RecordingReader<'a, R> {
+    inner: R,
+    record: &'a mut BodyRecord,
+}
+
+#[cfg(feature = "blocking")]
+impl<R: std::io::Read> std::io::Read for RecordingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.record.record(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    /// Decodes a body received in a single chunk.
+    fn decode<T: Decode>(status_code: StatusCode, headers: &HeaderMap, body: &[u8]) -> Result<T> {
+        let mut record = BodyRecord::default();
+        record.record(body);
+        let mut decoder = T::decoder();
+        let result = decoder.feed(body).and_then(|()| decoder.finish());
+        record.decode(status_code, headers, result)
+    }
+
+    fn headers(content_type: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers
+    }
+
+    #[test]
+    fn test_decode() -> Result<()> {
+        let json = headers("application/json; charset=utf-8");
+        let r: RowAffected = decode(StatusCode::OK, &json, br#"{"RowAffected":2}"#)?;
+        assert_eq!(r.row_affected, 2);
+        let r: RowAffected = decode(StatusCode::NO_CONTENT, &HeaderMap::new(), b"")?;
+        assert_eq!(r.row_affected, 0);
+        let scores: Vec<String> = decode(StatusCode::CREATED, &json, b" ")?;
+        assert!(scores.is_empty());
+        assert!(matches!(
+            decode::<User>(StatusCode::NO_CONTENT, &json, b""),
+            Err(Error::Decode { .. })
+        ));
+
+        let html = "<html>".repeat(100);
+        match decode::<RowAffected>(StatusCode::OK, &headers("text/html"), html.as_bytes()) {
+            Err(Error::Decode { reason, body, .. }) => {
+                assert_eq!(reason, "unexpected content type text/html");
+                assert_eq!(body.len(), SNIPPET_LEN + 3);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match decode::<RowAffected>(StatusCode::OK, &HeaderMap::new(), b"not json") {
+            Err(Error::Decode { body, .. }) => assert_eq!(body, "not json"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,382 @@
+//! [`tower`] integration for the async client.
+//!
+//! The client can send its requests through any
+//! `tower::Service<http::Request<reqwest::Body>>`, see
+//! [`GorseBuilder::service`](crate::GorseBuilder::service), and is itself a
+//! `tower::Service` for each of the typed requests in this module, so that
+//! tower layers such as timeouts, rate limits or load shedding apply to Gorse
+//! calls:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! use std::time::Duration;
+//! use gorse_rs::service::GetRecommend;
+//! use gorse_rs::Gorse;
+//! use tower::{ServiceBuilder, ServiceExt};
+//!
+//! let client = Gorse::new("http://127.0.0.1:8087", "api_key");
+//! let service = ServiceBuilder::new()
+//!     .timeout(Duration::from_millis(100))
+//!     .service(client);
+//! let scores = service
+//!     .oneshot(GetRecommend { user_id: "bob".into(), n: 10 })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use ::tower::util::BoxCloneSyncService;
+use ::tower::{Service, ServiceExt};
+use bytes::Bytes;
+use reqwest::{Body, Response};
+
+use crate::{
+    Error, Feedback, Gorse, HealthStatus, Item, RecommendOptions, Result, RowAffected, Score, User,
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type BoxFuture<T> = Pin<Box<dyn Future<Output = Result<T>> + Send>>;
+
+/// HTTP service the async client sends its requests through, within the
+/// timeout of each request, or else of the client.
+#[derive(Clone)]
+pub(crate) struct HttpService {
+    inner: BoxCloneSyncService<http::Request<Body>, http::Response<Body>, BoxError>,
+    timeout: Option<Duration>,
+}
+
+impl HttpService {
+    pub(crate) fn new<S, B>(service: S) -> Self
+    where
+        S: Service<http::Request<Body>, Response = http::Response<B>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Error: Into<BoxError>,
+        S::Future: Send + 'static,
+        B: http_body::Body + Send + Sync + 'static,
+        B::Data: Into<Bytes>,
+        B::Error: Into<BoxError>,
+    {
+        Self {
+            inner: BoxCloneSyncService::new(
+                service
+                    .map_response(|response: http::Response<B>| response.map(Body::wrap))
+                    .map_err(Into::into),
+            ),
+            timeout: None,
+        }
+    }
+
+    pub(crate) fn with_timeout(self, timeout: Option<Duration>) -> Self {
+        Self { timeout, ..self }
+    }
+
+    pub(crate) async fn call(&self, request: reqwest::Request) -> Result<Response> {
+        let timeout = request.timeout().copied().or(self.timeout);
+        let request = http::Request::try_from(request)?;
+        let response = self.inner.clone().oneshot(request);
+        let response = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, response)
+                .await
+                .map_err(|elapsed| Error::Service(Box::new(elapsed)))?,
+            None => response.await,
+        };
+        Ok(Response::from(response.map_err(Error::Service)?))
+    }
+}
+
+impl fmt::Debug for HttpService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HttpService")
+    }
+}
+
+/// A typed request to a Gorse endpoint, sent by calling the client as a
+/// `tower::Service`.
+pub trait Operation: Send + 'static {
+    type Response: Send + 'static;
+
+    /// Sends the request with the client.
+    fn send(self, client: Gorse) -> BoxFuture<Self::Response>;
+}
+
+impl<O: Operation> Service<O> for Gorse {
+    type Response = O::Response;
+    type Error = Error;
+    type Future = BoxFuture<O::Response>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, operation: O) -> Self::Future {
+        operation.send(self.clone())
+    }
+}
+
+macro_rules! operation {
+    ($operation:ident => $response:ty, |$client:ident, $request:ident| $call:expr) => {
+        impl Operation for $operation {
+            type Response = $response;
+
+            fn send(self, $client: Gorse) -> BoxFuture<$response> {
+                let $request = self;
+                Box::pin(async move { $call.await })
+            }
+        }
+    };
+}
+
+/// Request for [`Gorse::insert_user`].
+#[derive(Debug)]
+pub struct InsertUser(pub User);
+operation!(InsertUser => RowAffected, |client, request| client.insert_user(&request.0));
+
+/// Request for [`Gorse::get_user`].
+#[derive(Debug, Clone)]
+pub struct GetUser {
+    pub user_id: String,
+}
+operation!(GetUser => User, |client, request| client.get_user(&request.user_id));
+
+/// Request for [`Gorse::delete_user`].
+#[derive(Debug, Clone)]
+pub struct DeleteUser {
+    pub user_id: String,
+}
+operation!(DeleteUser => RowAffected, |client, request| client.delete_user(&request.user_id));
+
+/// Request for [`Gorse::insert_item`].
+#[derive(Debug)]
+pub struct InsertItem(pub Item);
+operation!(InsertItem => RowAffected, |client, request| client.insert_item(&request.0));
+
+/// Request for [`Gorse::get_item`].
+#[derive(Debug, Clone)]
+pub struct GetItem {
+    pub item_id: String,
+}
+operation!(GetItem => Item, |client, request| client.get_item(&request.item_id));
+
+/// Request for [`Gorse::delete_item`].
+#[derive(Debug, Clone)]
+pub struct DeleteItem {
+    pub item_id: String,
+}
+operation!(DeleteItem => RowAffected, |client, request| client.delete_item(&request.item_id));
+
+/// Request for [`Gorse::insert_feedback`].
+#[derive(Debug, Clone)]
+pub struct InsertFeedback(pub Vec<Feedback>);
+operation!(InsertFeedback => RowAffected, |client, request| client.insert_feedback(&request.0));
+
+/// Request for [`Gorse::upsert_feedback`].
+#[derive(Debug, Clone)]
+pub struct UpsertFeedback(pub Vec<Feedback>);
+operation!(UpsertFeedback => RowAffected, |client, request| client.upsert_feedback(&request.0));
+
+/// Request for [`Gorse::delete_feedback`].
+#[derive(Debug, Clone)]
+pub struct DeleteFeedback {
+    pub user_id: String,
+    pub item_id: String,
+}
+operation!(DeleteFeedback => RowAffected, |client, request| {
+    client.delete_feedback(&request.user_id, &request.item_id)
+});
+
+/// Request for [`Gorse::list_feedback`].
+#[derive(Debug, Clone)]
+pub struct ListFeedback {
+    pub user_id: String,
+    pub feedback_type: String,
+}
+operation!(ListFeedback => Vec<Feedback>, |client, request| {
+    client.list_feedback(&request.user_id, &request.feedback_type)
+});
+
+/// Request for [`Gorse::get_item_neighbors`].
+#[derive(Debug, Clone)]
+pub struct GetItemNeighbors {
+    pub item_id: String,
+}
+operation!(GetItemNeighbors => Vec<Score>, |client, request| {
+    client.get_item_neighbors(&request.item_id)
+});
+
+/// Request for [`Gorse::get_recommend`].
+#[derive(Debug, Clone)]
+pub struct GetRecommend {
+    pub user_id: String,
+    pub n: usize,
+}
+operation!(GetRecommend => Vec<Score>, |client, request| {
+    client.get_recommend(&request.user_id, RecommendOptions { n: request.n })
+});
+
+/// Request for [`Gorse::get_recommend_ids`].
+#[derive(Debug, Clone)]
+pub struct GetRecommendIds {
+    pub user_id: String,
+    pub n: usize,
+}
+operation!(GetRecommendIds => Vec<String>, |client, request| {
+    client.get_recommend_ids(&request.user_id, RecommendOptions { n: request.n })
+});
+
+/// Request for [`Gorse::get_popular`].
+#[derive(Debug, Clone)]
+pub struct GetPopular {
+    pub category: Option<String>,
+    pub n: usize,
+}
+operation!(GetPopular => Vec<Score>, |client, request| {
+    client.get_popular(request.category.as_deref(), RecommendOptions { n: request.n })
+});
+
+/// Request for [`Gorse::get_latest`].
+#[derive(Debug, Clone)]
+pub struct GetLatest {
+    pub category: Option<String>,
+    pub n: usize,
+}
+operation!(GetLatest => Vec<Score>, |client, request| {
+    client.get_latest(request.category.as_deref(), RecommendOptions { n: request.n })
+});
+
+/// Request for [`Gorse::get_item_to_item`].
+#[derive(Debug, Clone)]
+pub struct GetItemToItem {
+    pub name: String,
+    pub item_id: String,
+    pub n: usize,
+}
+operation!(GetItemToItem => Vec<Score>, |client, request| {
+    client.get_item_to_item(&request.name, &request.item_id, RecommendOptions { n: request.n })
+});
+
+/// Request for [`Gorse::get_user_to_user`].
+#[derive(Debug, Clone)]
+pub struct GetUserToUser {
+    pub name: String,
+    pub user_id: String,
+    pub n: usize,
+}
+operation!(GetUserToUser => Vec<Score>, |client, request| {
+    client.get_user_to_user(&request.name, &request.user_id, RecommendOptions { n: request.n })
+});
+
+/// Request for [`Gorse::get_collaborative_filtering`].
+#[derive(Debug, Clone)]
+pub struct GetCollaborativeFiltering {
+    pub user_id: String,
+    pub n: usize,
+}
+operation!(GetCollaborativeFiltering => Vec<Score>, |client, request| {
+    client.get_collaborative_filtering(&request.user_id, RecommendOptions { n: request.n })
+});
+
+/// Request for [`Gorse::get_non_personalized`].
+#[derive(Debug, Clone)]
+pub struct GetNonPersonalized {
+    pub name: String,
+    pub n: usize,
+}
+operation!(GetNonPersonalized => Vec<Score>, |client, request| {
+    client.get_non_personalized(&request.name, RecommendOptions { n: request.n })
+});
+
+/// Request for [`Gorse::health_live`].
+#[derive(Debug, Clone)]
+pub struct HealthLive;
+operation!(HealthLive => HealthStatus, |client, _request| client.health_live());
+
+/// Request for [`Gorse::health_ready`].
+#[derive(Debug, Clone)]
+pub struct HealthReady;
+operation!(HealthReady => HealthStatus, |client, _request| client.health_ready());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_service() -> Result<()> {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let service = ::tower::service_fn(move |request: http::Request<Body>| {
+            recorded.lock().unwrap().push((
+                request.uri().to_string(),
+                request.headers()["X-API-Key"].clone(),
+            ));
+            async move {
+                let body = r#"{"UserId":"1","Labels":null,"Comment":""}"#.to_string();
+                Ok::<_, Infallible>(http::Response::new(body))
+            }
+        });
+        let client = Gorse::builder()
+            .entry_point("http://gorse/")
+            .api_key("api_key")
+            .service(service)
+            .build()?;
+
+        let user = client.get_user("1").await?;
+        assert_eq!(user.user_id, "1");
+        let user = client
+            .clone()
+            .oneshot(GetUser {
+                user_id: "2".into(),
+            })
+            .await?;
+        assert_eq!(user.user_id, "1");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].0, "http://gorse/api/user/2");
+        assert_eq!(requests[1].1, "api_key");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_service_timeout() -> Result<()> {
+        let service = ::tower::service_fn(|_: http::Request<Body>| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        });
+        let client = Gorse::builder()
+            .entry_point("http://gorse/")
+            .timeout(Duration::from_millis(10))
+            .service(service)
+            .build()?;
+        assert!(matches!(client.get_user("1").await, Err(Error::Service(_))));
+
+        let builder = Gorse::builder()
+            .entry_point("http://gorse/")
+            .connect_timeout(Duration::from_secs(1))
+            .service(service);
+        assert!(matches!(builder.build(), Err(Error::Config(_))));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_blocking_service() {
+        let service = ::tower::service_fn(|_: http::Request<Body>| async {
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        });
+        let builder = Gorse::builder()
+            .entry_point("http://gorse/")
+            .service(service);
+        assert!(matches!(builder.build_blocking(), Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,227 @@
+//! Splits of feedback into train feedback, to insert, and test feedback, to
+//! evaluate recommendations against, e.g. with
+//! [`Gorse::evaluate`](crate::Gorse::evaluate).
+//!
+//! ```
+//! use gorse_rs::split;
+//! # let feedback = Vec::new();
+//!
+//! let split = split::leave_last_out(feedback, 1);
+//! println!("{} train, {} test", split.train.len(), split.test.len());
+//! ```
+//!
+//! Splits are deterministic: random splits depend only on their seed, and
+//! both halves keep the order of the input.
+
+use std::collections::HashMap;
+
+use crate::{timestamp, Feedback};
+
+/// Feedback split into train and test feedback.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Split {
+    pub train: Vec<Feedback>,
+    pub test: Vec<Feedback>,
+}
+
+impl Split {
+    /// Splits feedback by whether each is test feedback.
+    fn from_flags(feedback: Vec<Feedback>, is_test: &[bool]) -> Self {
+        let mut split = Split::default();
+        for (feedback, &is_test) in feedback.into_iter().zip(is_test) {
+            if is_test {
+                split.test.push(feedback);
+            } else {
+                split.train.push(feedback);
+            }
+        }
+        split
+    }
+}
+
+/// Holds out the last `k` feedback of each user by timestamp. Users with
+/// `k` feedback or less are kept in the train feedback. Feedback without a
+/// valid timestamp is the oldest, and feedback with equal timestamps keeps
+/// its order.
+pub fn leave_last_out(feedback: Vec<Feedback>, k: usize) -> Split {
+    let mut is_test = vec![false; feedback.len()];
+    for mut indices in indices_by_user(&feedback) {
+        if indices.len() <= k {
+            continue;
+        }
+        indices.sort_by_key(|&index| timestamp::parse(&feedback[index].timestamp));
+        for &index in &indices[indices.len() - k..] {
+            is_test[index] = true;
+        }
+    }
+    Split::from_flags(feedback, &is_test)
+}
+
+/// Holds out a random `test_ratio` of the feedback, rounded to the nearest
+/// count.
+pub fn random_split(feedback: Vec<Feedback>, test_ratio: f64, seed: u64) -> Split {
+    let mut indices: Vec<usize> = (0..feedback.len()).collect();
+    SplitMix64::new(seed).shuffle(&mut indices);
+    let mut is_test = vec![false; feedback.len()];
+    for &index in &indices[..test_count(feedback.len(), test_ratio)] {
+        is_test[index] = true;
+    }
+    Split::from_flags(feedback, &is_test)
+}
+
+/// Holds out the feedback at or after `cutoff`, an RFC 3339 timestamp, e.g.
+/// `2022-01-01T00:00:00Z`, or a date, e.g. `2022-01-01`. Feedback without a
+/// valid timestamp is train feedback. Returns `None` for an invalid cutoff.
+pub fn time_split(feedback: Vec<Feedback>, cutoff: &str) -> Option<Split> {
+    let cutoff = timestamp::parse(cutoff)?;
+    let is_test: Vec<bool> = feedback
+        .iter()
+        .map(|feedback| timestamp::parse(&feedback.timestamp).is_some_and(|time| time >= cutoff))
+        .collect();
+    Some(Split::from_flags(feedback, &is_test))
+}
+
+/// Holds out a random `test_ratio` of the feedback of each user, rounded
+/// to the nearest count, so that every user is in both halves. Users keep
+/// at least one train feedback.
+pub fn user_split(feedback: Vec<Feedback>, test_ratio: f64, seed: u64) -> Split {
+    let mut rng = SplitMix64::new(seed);
+    let mut is_test = vec![false; feedback.len()];
+    for mut indices in indices_by_user(&feedback) {
+        rng.shuffle(&mut indices);
+        let count = test_count(indices.len(), test_ratio).min(indices.len() - 1);
+        for &index in &indices[..count] {
+            is_test[index] = true;
+        }
+    }
+    Split::from_flags(feedback, &is_test)
+}
+
+/// Indices of the feedback of each user, in the order of their first
+/// feedback.
+fn indices_by_user(feedback: &[Feedback]) -> Vec<Vec<usize>> {
+    let mut users: HashMap<&str, usize> = HashMap::new();
+    let mut indices: Vec<Vec<usize>> = Vec::new();
+    for (index, feedback) in feedback.iter().enumerate() {
+        let user = *users.entry(&feedback.user_id).or_insert_with(|| {
+            indices.push(Vec::new());
+            indices.len() - 1
+        });
+        indices[user].push(index);
+    }
+    indices
+}
+
+fn test_count(len: usize, test_ratio: f64) -> usize {
+    ((len as f64 * test_ratio.clamp(0.0, 1.0)).round() as usize).min(len)
+}
+
+/// SplitMix64 pseudo-random generator, so that splits of a seed are the
+/// same across platforms and releases.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Fisher-Yates shuffle.
+    fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            values.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback(user_id: &str, item_id: &str, timestamp: &str) -> Feedback {
+        Feedback {
+            feedback_type: "star".into(),
+            user_id: user_id.into(),
+            item_id: item_id.into(),
+            value: 1.0,
+            timestamp: timestamp.into(),
+        }
+    }
+
+    fn items(feedback: &[Feedback]) -> Vec<&str> {
+        feedback
+            .iter()
+            .map(|feedback| feedback.item_id.as_str())
+            .collect()
+    }
+
+    fn data() -> Vec<Feedback> {
+        vec![
+            feedback("a", "1", "2022-01-03T00:00:00Z"),
+            feedback("a", "2", "2022-01-01T00:00:00Z"),
+            feedback("b", "3", "2022-01-02T00:00:00+08:00"),
+            feedback("a", "4", "2022-01-02T00:00:00Z"),
+            feedback("b", "5", ""),
+            feedback("c", "6", "2022-01-04"),
+        ]
+    }
+
+    #[test]
+    fn test_splitmix64() {
+        // Reference outputs of SplitMix64 seeded with 1234567.
+        let mut rng = SplitMix64::new(1234567);
+        assert_eq!(rng.next_u64(), 6457827717110365317);
+        assert_eq!(rng.next_u64(), 3203168211198807973);
+    }
+
+    #[test]
+    fn test_leave_last_out() {
+        let split = leave_last_out(data(), 1);
+        assert_eq!(items(&split.train), vec!["2", "4", "5", "6"]);
+        assert_eq!(items(&split.test), vec!["1", "3"]);
+        assert_eq!(leave_last_out(data(), 3).test.len(), 0);
+    }
+
+    #[test]
+    fn test_random_split() {
+        let split = random_split(data(), 0.5, 42);
+        assert_eq!((split.train.len(), split.test.len()), (3, 3));
+        assert_eq!(random_split(data(), 0.5, 42), split);
+        assert_ne!(random_split(data(), 0.5, 43), split);
+        assert!(random_split(data(), 0.0, 42).test.is_empty());
+        assert!(random_split(data(), 1.0, 42).train.is_empty());
+    }
+
+    #[test]
+    fn test_time_split() {
+        let split = time_split(data(), "2022-01-02").unwrap();
+        assert_eq!(items(&split.train), vec!["2", "3", "5"]);
+        assert_eq!(items(&split.test), vec!["1", "4", "6"]);
+        assert!(time_split(data(), "yesterday").is_none());
+    }
+
+    #[test]
+    fn test_user_split() {
+        let split = user_split(data(), 0.5, 7);
+        assert_eq!(user_split(data(), 0.5, 7), split);
+        // a holds out 2 of 3, b 1 of 2 and c none of 1.
+        assert_eq!(split.test.len(), 3);
+        assert!(split.test.iter().all(|feedback| feedback.user_id != "c"));
+        assert_eq!(
+            split
+                .test
+                .iter()
+                .filter(|feedback| feedback.user_id == "b")
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,749 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use crate::{Error, Gorse, Page, Result};
+
+/// Tracks the strings and nesting of JSON values fed byte by byte.
+#[derive(Debug, Default)]
+struct Scanner {
+    /// Nesting depth inside the value.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Scanner {
+    /// Returns whether a byte ends the value, i.e. is a `,`, `]` or `}` of
+    /// the enclosing array or object.
+    fn ends_value(&mut self, byte: u8) -> Result<bool> {
+        if self.in_string {
+            match (self.escaped, byte) {
+                (true, _) => self.escaped = false,
+                (false, b'\\') => self.escaped = true,
+                (false, b'"') => self.in_string = false,
+                _ => {}
+            }
+            return Ok(false);
+        }
+        match byte {
+            b',' | b']' | b'}' if self.depth == 0 => return Ok(true),
+            b'[' | b'{' => self.depth += 1,
+            b']' | b'}' => {
+                self.depth = self
+                    .depth
+                    .checked_sub(1)
+                    .ok_or_else(|| invalid("unbalanced brackets"))?
+            }
+            b'"' => self.in_string = true,
+            _ => {}
+        }
+        Ok(false)
+    }
+}
+
+/// Splits a JSON array arriving in chunks into its elements, so that each
+/// element can be decoded as soon as it is complete. Only the element being
+/// received is buffered.
+#[derive(Debug, Default)]
+pub(crate) struct ArraySplitter {
+    state: SplitState,
+    scanner: Scanner,
+    element: Vec<u8>,
+    /// Whether an element was completed, to tell `[]` from `[1,]`.
+    has_elements: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+enum SplitState {
+    #[default]
+    Start,
+    Elements,
+    Done,
+}
+
+impl ArraySplitter {
+    /// Feeds a chunk of the response body and appends the completed elements.
+    pub(crate) fn feed(&mut self, chunk: &[u8], elements: &mut VecDeque<Vec<u8>>) -> Result<()> {
+        for &byte in chunk {
+            match self.state {
+                SplitState::Start => match byte {
+                    b'[' => self.state = SplitState::Elements,
+                    byte if byte.is_ascii_whitespace() => {}
+                    _ => return Err(invalid("expected a JSON array")),
+                },
+                SplitState::Elements => self.feed_element(byte, elements)?,
+                SplitState::Done => {
+                    if !byte.is_ascii_whitespace() {
+                        return Err(invalid("trailing characters after the JSON array"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn feed_element(&mut self, byte: u8, elements: &mut VecDeque<Vec<u8>>) -> Result<()> {
+        if !self.scanner.ends_value(byte)? {
+            self.element.push(byte);
+            return Ok(());
+        }
+        if byte == b'}' {
+            return Err(invalid("unbalanced brackets"));
+        }
+        let element = std::mem::take(&mut self.element);
+        let is_empty = element.iter().all(u8::is_ascii_whitespace);
+        if byte == b']' {
+            self.state = SplitState::Done;
+            if is_empty && !self.has_elements {
+                return Ok(());
+            }
+        }
+        if is_empty {
+            return Err(invalid("missing array element"));
+        }
+        self.has_elements = true;
+        elements.push_back(element);
+        Ok(())
+    }
+
+    fn is_done(&self) -> bool {
+        self.state == SplitState::Done
+    }
+
+    /// Checks that the whole array was received. An empty body, e.g. from a
+    /// `204 No Content` response, is an empty array.
+    pub(crate) fn finish(&self) -> Result<()> {
+        if self.state != SplitState::Elements {
+            Ok(())
+        } else {
+            Err(invalid("unexpected end of the JSON array"))
+        }
+    }
+}
+
+/// Splits a page of a listing arriving in chunks, e.g.
+/// `{"Cursor":"...","Feedback":[...]}`, into its records, as they are
+/// complete, and its cursor. Only the record or field being received is
+/// buffered.
+#[derive(Debug, Default)]
+pub(crate) struct PageSplitter {
+    state: PageState,
+    key: Vec<u8>,
+    escaped: bool,
+    value: Vec<u8>,
+    scanner: Scanner,
+    records: ArraySplitter,
+    cursor: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+enum PageState {
+    #[default]
+    Start,
+    BeforeKey,
+    Key,
+    Colon,
+    BeforeValue,
+    Value,
+    Records,
+    AfterValue,
+    Done,
+}
+
+impl PageSplitter {
+    /// Feeds a chunk of the response body and appends the completed records.
+    pub(crate) fn feed(&mut self, chunk: &[u8], records: &mut VecDeque<Vec<u8>>) -> Result<()> {
+        for &byte in chunk {
+            self.feed_byte(byte, records)?;
+        }
+        Ok(())
+    }
+
+    fn feed_byte(&mut self, byte: u8, records: &mut VecDeque<Vec<u8>>) -> Result<()> {
+        let whitespace = byte.is_ascii_whitespace();
+        match self.state {
+            PageState::Start | PageState::Done if whitespace => {}
+            PageState::Start if byte == b'{' => self.state = PageState::BeforeKey,
+            PageState::Start => return Err(invalid("expected a JSON object")),
+            PageState::BeforeKey if whitespace => {}
+            PageState::BeforeKey if byte == b'"' => {
+                self.key.clear();
+                self.state = PageState::Key;
+            }
+            PageState::BeforeKey if byte == b'}' => self.state = PageState::Done,
+            PageState::BeforeKey => return Err(invalid("expected a key")),
+            PageState::Key => match (self.escaped, byte) {
+                (false, b'"') => self.state = PageState::Colon,
+                (escaped, byte) => {
+                    self.escaped = !escaped && byte == b'\\';
+                    self.key.push(byte);
+                }
+            },
+            PageState::Colon if whitespace => {}
+            PageState::Colon if byte == b':' => self.state = PageState::BeforeValue,
+            PageState::Colon => return Err(invalid("expected a colon")),
+            PageState::BeforeValue if whitespace => {}
+            PageState::BeforeValue if byte == b'[' && self.is_records() => {
+                self.records = ArraySplitter::default();
+                self.records.feed(&[byte], records)?;
+                self.state = PageState::Records;
+            }
+            PageState::BeforeValue => {
+                self.value.clear();
+                self.scanner = Scanner::default();
+                self.state = PageState::Value;
+                self.feed_byte(byte, records)?;
+            }
+            PageState::Value => {
+                if !self.scanner.ends_value(byte)? {
+                    self.value.push(byte);
+                    return Ok(());
+                }
+                self.end_value()?;
+                self.state = PageState::AfterValue;
+                self.feed_byte(byte, records)?;
+            }
+            PageState::Records => {
+                self.records.feed(&[byte], records)?;
+                if self.records.is_done() {
+                    self.state = PageState::AfterValue;
+                }
+            }
+            PageState::AfterValue if whitespace => {}
+            PageState::AfterValue if byte == b',' => self.state = PageState::BeforeKey,
+            PageState::AfterValue if byte == b'}' => self.state = PageState::Done,
+            PageState::AfterValue => return Err(invalid("expected a comma")),
+            PageState::Done => return Err(invalid("trailing characters after the JSON object")),
+        }
+        Ok(())
+    }
+
+    /// Whether the current key is the field of the records, see [`Page`].
+    fn is_records(&self) -> bool {
+        matches!(&self.key[..], b"Users" | b"Items" | b"Feedback")
+    }
+
+    fn end_value(&mut self) -> Result<()> {
+        if self.key == b"Cursor" {
+            self.cursor = Some(serde_json::from_slice(&self.value)?);
+        } else if self.is_records() {
+            // A page without records may have `null` records.
+            serde_json::from_slice::<()>(&self.value)?;
+        } else {
+            serde_json::from_slice::<serde::de::IgnoredAny>(&self.value)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the whole page was received and returns its cursor.
+    pub(crate) fn finish(self) -> Result<String> {
+        if self.state != PageState::Done {
+            return Err(invalid("unexpected end of the JSON object"));
+        }
+        self.cursor.ok_or_else(|| invalid("missing field `Cursor`"))
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Serde(serde::de::Error::custom(message))
+}
+
+/// Decoder of a response body fed in chunks as they arrive.
+pub(crate) trait BodyDecoder<T>: Send {
+    fn feed(&mut self, chunk: &[u8]) -> Result<()>;
+
+    fn finish(self: Box<Self>) -> Result<T>;
+}
+
+/// Decoder buffering the body, for small responses.
+pub(crate) struct BufferedDecoder<T> {
+    body: Vec<u8>,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for BufferedDecoder<T> {
+    fn default() -> Self {
+        Self {
+            body: Vec::new(),
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> BodyDecoder<T> for BufferedDecoder<T> {
+    fn feed(&mut self, chunk: &[u8]) -> Result<()> {
+        self.body.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// Decoder of a JSON array decoding each element as it is complete.
+pub(crate) struct ArrayDecoder<T> {
+    splitter: ArraySplitter,
+    elements: VecDeque<Vec<u8>>,
+    records: Vec<T>,
+}
+
+impl<T> Default for ArrayDecoder<T> {
+    fn default() -> Self {
+        Self {
+            splitter: ArraySplitter::default(),
+            elements: VecDeque::new(),
+            records: Vec::new(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Send> BodyDecoder<Vec<T>> for ArrayDecoder<T> {
+    fn feed(&mut self, chunk: &[u8]) -> Result<()> {
+        self.splitter.feed(chunk, &mut self.elements)?;
+        for element in self.elements.drain(..) {
+            self.records.push(serde_json::from_slice(&element)?);
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vec<T>> {
+        self.splitter.finish()?;
+        Ok(self.records)
+    }
+}
+
+/// Decoder of a page of a listing decoding each record as it is complete.
+pub(crate) struct PageDecoder<T> {
+    splitter: PageSplitter,
+    elements: VecDeque<Vec<u8>>,
+    records: Vec<T>,
+}
+
+impl<T> Default for PageDecoder<T> {
+    fn default() -> Self {
+        Self {
+            splitter: PageSplitter::default(),
+            elements: VecDeque::new(),
+            records: Vec::new(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Send> BodyDecoder<Page<T>> for PageDecoder<T> {
+    fn feed(&mut self, chunk: &[u8]) -> Result<()> {
+        self.splitter.feed(chunk, &mut self.elements)?;
+        for element in self.elements.drain(..) {
+            self.records.push(serde_json::from_slice(&element)?);
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Page<T>> {
+        Ok(Page {
+            cursor: self.splitter.finish()?,
+            records: self.records,
+        })
+    }
+}
+
+/// Elements of a JSON array response, decoded as they arrive, e.g. from
+/// [`Gorse::stream_feedback`](crate::Gorse::stream_feedback).
+///
+/// ```no_run
+/// # async fn run(client: gorse_rs::Gorse) -> Result<(), gorse_rs::Error> {
+/// let mut feedback = client.stream_feedback("bob", "star").await?;
+/// while let Some(feedback) = feedback.next().await? {
+///     println!("{}", feedback.item_id);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct JsonArrayStream<T> {
+    response: reqwest::Response,
+    splitter: ArraySplitter,
+    elements: VecDeque<Vec<u8>>,
+    finished: bool,
+    element: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> JsonArrayStream<T> {
+    pub(crate) fn new(response: reqwest::Response) -> Self {
+        Self {
+            response,
+            splitter: ArraySplitter::default(),
+            elements: VecDeque::new(),
+            finished: false,
+            element: PhantomData,
+        }
+    }
+
+    /// Returns the next element, or `None` at the end of the array.
+    pub async fn next(&mut self) -> Result<Option<T>> {
+        loop {
+            if let Some(element) = self.elements.pop_front() {
+                return Ok(Some(serde_json::from_slice(&element)?));
+            }
+            if self.finished {
+                return Ok(None);
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.splitter.feed(&chunk, &mut self.elements)?,
+                None => {
+                    self.finished = true;
+                    self.splitter.finish()?;
+                }
+            }
+        }
+    }
+}
+
+/// Elements of a JSON array response, decoded as they are read, e.g. from
+/// [`blocking::Gorse::stream_feedback`](crate::blocking::Gorse::stream_feedback).
+#[cfg(feature = "blocking")]
+#[derive(Debug)]
+pub struct JsonArrayIter<T> {
+    response: reqwest::blocking::Response,
+    splitter: ArraySplitter,
+    elements: VecDeque<Vec<u8>>,
+    buffer: Box<[u8]>,
+    finished: bool,
+    element: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "blocking")]
+impl<T: DeserializeOwned> JsonArrayIter<T> {
+    pub(crate) fn new(response: reqwest::blocking::Response) -> Self {
+        Self {
+            response,
+            splitter: ArraySplitter::default(),
+            elements: VecDeque::new(),
+            buffer: vec![0; 8192].into_boxed_slice(),
+            finished: false,
+            element: PhantomData,
+        }
+    }
+
+    fn read_next(&mut self) -> Result<Option<T>> {
+        use std::io::Read;
+
+        loop {
+            if let Some(element) = self.elements.pop_front() {
+                return Ok(Some(serde_json::from_slice(&element)?));
+            }
+            if self.finished {
+                return Ok(None);
+            }
+            match self.response.read(&mut self.buffer)? {
+                0 => {
+                    self.finished = true;
+                    self.splitter.finish()?;
+                }
+                n => self.splitter.feed(&self.buffer[..n], &mut self.elements)?,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<T: DeserializeOwned> Iterator for JsonArrayIter<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        let next = self.read_next();
+        if next.is_err() {
+            self.finished = true;
+            self.elements.clear();
+        }
+        next.transpose()
+    }
+}
+
+/// Pages of a listing requested one after the other, following their
+/// cursors.
+#[derive(Debug)]
+struct Pages {
+    endpoint: &'static str,
+    path: &'static str,
+    n: usize,
+    /// Cursor of the next page, `None` after the last page.
+    cursor: Option<String>,
+    splitter: PageSplitter,
+    elements: VecDeque<Vec<u8>>,
+}
+
+impl Pages {
+    fn new(endpoint: &'static str, path: &'static str, n: usize) -> Self {
+        Self {
+            endpoint,
+            path,
+            n,
+            cursor: Some(String::new()),
+            splitter: PageSplitter::default(),
+            elements: VecDeque::new(),
+        }
+    }
+
+    /// Path of the next page, if any.
+    fn next_path(&mut self) -> Option<String> {
+        let cursor = self.cursor.take()?;
+        Some(crate::page_path(self.path, &cursor, self.n))
+    }
+
+    fn feed(&mut self, chunk: &[u8]) -> Result<()> {
+        self.splitter.feed(chunk, &mut self.elements)
+    }
+
+    /// Reads the cursor of the next page once a page was received.
+    fn end_page(&mut self) -> Result<()> {
+        let cursor = std::mem::take(&mut self.splitter).finish()?;
+        self.cursor = Some(cursor).filter(|cursor| !cursor.is_empty());
+        Ok(())
+    }
+
+    fn next_element<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        self.elements
+            .pop_front()
+            .map(|element| Ok(serde_json::from_slice(&element)?))
+            .transpose()
+    }
+
+    fn stop(&mut self) {
+        self.cursor = None;
+        self.elements.clear();
+    }
+}
+
+/// Records of a listing, requested by pages and decoded as they arrive,
+/// e.g. from [`Gorse::stream_all_feedback`].
+///
+/// ```no_run
+/// # async fn run(client: gorse_rs::Gorse) -> Result<(), gorse_rs::Error> {
+/// let mut feedback = client.stream_all_feedback(1000);
+/// while let Some(feedback) = feedback.next().await? {
+///     println!("{} {}", feedback.user_id, feedback.item_id);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PageStream<T> {
+    client: Gorse,
+    pages: Pages,
+    /// Response of the page being received.
+    response: Option<reqwest::Response>,
+    record: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> PageStream<T> {
+    pub(crate) fn new(client: Gorse, endpoint: &'static str, path: &'static str, n: usize) -> Self {
+        Self {
+            client,
+            pages: Pages::new(endpoint, path, n),
+            response: None,
+            record: PhantomData,
+        }
+    }
+
+    /// Returns the next record, or `None` after the last page.
+    pub async fn next(&mut self) -> Result<Option<T>> {
+        let next = self.read_next().await;
+        if next.is_err() {
+            self.response = None;
+            self.pages.stop();
+        }
+        next
+    }
+
+    async fn read_next(&mut self) -> Result<Option<T>> {
+        loop {
+            if let Some(record) = self.pages.next_element()? {
+                return Ok(Some(record));
+            }
+            match &mut self.response {
+                Some(response) => match response.chunk().await? {
+                    Some(chunk) => self.pages.feed(&chunk)?,
+                    None => {
+                        self.response = None;
+                        self.pages.end_page()?;
+                    }
+                },
+                None => match self.pages.next_path() {
+                    Some(path) => {
+                        let response = self.client.open_stream(self.pages.endpoint, path).await?;
+                        self.response = Some(response);
+                    }
+                    None => return Ok(None),
+                },
+            }
+        }
+    }
+}
+
+/// Records of a listing, requested by pages and decoded as they are read,
+/// e.g. from
+/// [`blocking::Gorse::stream_all_feedback`](crate::blocking::Gorse::stream_all_feedback).
+#[cfg(feature = "blocking")]
+#[derive(Debug)]
+pub struct PageIter<T> {
+    client: crate::blocking::Gorse,
+    pages: Pages,
+    /// Response of the page being received.
+    response: Option<reqwest::blocking::Response>,
+    buffer: Box<[u8]>,
+    record: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "blocking")]
+impl<T: DeserializeOwned> PageIter<T> {
+    pub(crate) fn new(
+        client: crate::blocking::Gorse,
+        endpoint: &'static str,
+        path: &'static str,
+        n: usize,
+    ) -> Self {
+        Self {
+            client,
+            pages: Pages::new(endpoint, path, n),
+            response: None,
+            buffer: vec![0; 8192].into_boxed_slice(),
+            record: PhantomData,
+        }
+    }
+
+    fn read_next(&mut self) -> Result<Option<T>> {
+        use std::io::Read;
+
+        loop {
+            if let Some(record) = self.pages.next_element()? {
+                return Ok(Some(record));
+            }
+            match &mut self.response {
+                Some(response) => match response.read(&mut self.buffer)? {
+                    0 => {
+                        self.response = None;
+                        self.pages.end_page()?;
+                    }
+                    n => self.pages.feed(&self.buffer[..n])?,
+                },
+                None => match self.pages.next_path() {
+                    Some(path) => {
+                        let response = self.client.open_stream(self.pages.endpoint, path)?;
+                        self.response = Some(response);
+                    }
+                    None => return Ok(None),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<T: DeserializeOwned> Iterator for PageIter<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        let next = self.read_next();
+        if next.is_err() {
+            self.response = None;
+            self.pages.stop();
+        }
+        next.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn split(chunks: &[&str]) -> Result<Vec<Value>> {
+        let mut splitter = ArraySplitter::default();
+        let mut elements = VecDeque::new();
+        for chunk in chunks {
+            splitter.feed(chunk.as_bytes(), &mut elements)?;
+        }
+        splitter.finish()?;
+        elements
+            .iter()
+            .map(|element| Ok(serde_json::from_slice(element)?))
+            .collect()
+    }
+
+    fn split_page(chunks: &[&str]) -> Result<(String, Vec<Value>)> {
+        let mut splitter = PageSplitter::default();
+        let mut records = VecDeque::new();
+        for chunk in chunks {
+            splitter.feed(chunk.as_bytes(), &mut records)?;
+        }
+        let records = records
+            .iter()
+            .map(|record| Ok(serde_json::from_slice(record)?))
+            .collect::<Result<_>>()?;
+        Ok((splitter.finish()?, records))
+    }
+
+    #[test]
+    fn test_split() -> Result<()> {
+        let body = r#" [{"Id":"a,]","Labels":["x",{"y":"\"}"}]}, 1 ,"s\\" , null] "#;
+        let expected: Vec<Value> = serde_json::from_str(body)?;
+        assert_eq!(split(&[body])?, expected);
+        // Split the body between every pair of bytes.
+        for i in 0..body.len() {
+            assert_eq!(split(&[&body[..i], &body[i..]])?, expected);
+        }
+        assert!(split(&[" [ ] "])?.is_empty());
+        assert!(split(&["", " "])?.is_empty());
+
+        assert!(split(&["[1,2"]).is_err());
+        assert!(split(&["[1,,2]"]).is_err());
+        assert!(split(&["{}"]).is_err());
+        assert!(split(&["[1]2"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_page() -> Result<()> {
+        let body =
+            r#" { "Extra" : {"a":[1,"}"]}, "Feedback" : [{"ItemId":"a,]"}, 2] ,"Cursor":"n\"1" } "#;
+        let expected = ("n\"1".to_string(), vec![json!({"ItemId": "a,]"}), json!(2)]);
+        assert_eq!(split_page(&[body])?, expected);
+        for i in 0..body.len() {
+            assert_eq!(split_page(&[&body[..i], &body[i..]])?, expected);
+        }
+        assert_eq!(
+            split_page(&[r#"{"Cursor":"","Users":null}"#])?,
+            (String::new(), Vec::new())
+        );
+        assert_eq!(
+            split_page(&[r#"{"Items":[],"Cursor":""}"#])?,
+            (String::new(), Vec::new())
+        );
+
+        assert!(split_page(&[r#"{"Feedback":[]}"#]).is_err());
+        assert!(split_page(&[r#"{"Cursor":"","Feedback":[1"#]).is_err());
+        assert!(split_page(&[r#"{"Cursor":"","Feedback":1}"#]).is_err());
+        assert!(split_page(&[r#"{"Cursor":1}"#]).is_err());
+        assert!(split_page(&[r#"{"Cursor":""} {}"#]).is_err());
+        assert!(split_page(&["[]"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_page_decoder() -> Result<()> {
+        let mut decoder: Box<dyn BodyDecoder<Page<Value>>> = Box::<PageDecoder<Value>>::default();
+        decoder.feed(br#"{"Cursor":"next","Users":[{"UserId":"#)?;
+        decoder.feed(br#""bob"}]}"#)?;
+        assert_eq!(
+            decoder.finish()?,
+            Page {
+                cursor: "next".into(),
+                records: vec![json!({"UserId": "bob"})],
+            }
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+
+use crate::observer::{Observers, RequestInfo, RequestOutcome};
+use crate::{Error, Feedback, HealthStatus, Item, Page, RowAffected, ServerInfo, User};
+
+/// Number of rows affected or returned by a call, or number of records in a
+/// request body, recorded by telemetry.
+pub(crate) trait Rows {
+    fn rows(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl Rows for RowAffected {
+    fn rows(&self) -> Option<usize> {
+        usize::try_from(self.row_affected).ok()
+    }
+}
+
+impl<T> Rows for Vec<T> {
+    fn rows(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T> Rows for [T] {
+    fn rows(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T> Rows for Page<T> {
+    fn rows(&self) -> Option<usize> {
+        Some(self.records.len())
+    }
+}
+
+impl Rows for () {}
+impl Rows for User {}
+impl Rows for Item {}
+impl Rows for Feedback {}
+impl Rows for HealthStatus {}
+impl Rows for ServerInfo {}
+
+#[derive(Default)]
+struct Stats {
+    status_code: Option<StatusCode>,
+    bytes_received: usize,
+    attempts: usize,
+}
+
+/// Telemetry of a single call to the Gorse API: notifies the observers and,
+/// with the `tracing` feature, wraps the call in a span. The API key is never
+/// recorded.
+pub(crate) struct RequestTelemetry<'a> {
+    request: RequestInfo,
+    observers: &'a Observers,
+    stats: Mutex<Stats>,
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<'a> RequestTelemetry<'a> {
+    pub(crate) fn start(
+        endpoint: &'static str,
+        method: &Method,
+        bytes_sent: usize,
+        body_size: usize,
+        batch_size: Option<usize>,
+        observers: &'a Observers,
+    ) -> Self {
+        let request = RequestInfo {
+            endpoint,
+            method: method.clone(),
+            bytes_sent,
+            body_size,
+            batch_size,
+        };
+        for observer in &observers.0 {
+            observer.on_request_start(&request);
+        }
+        Self {
+            request,
+            observers,
+            stats: Mutex::new(Stats {
+                attempts: 1,
+                ..Default::default()
+            }),
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "gorse.request",
+                endpoint,
+                http.method = %method,
+                http.request_size = bytes_sent,
+                http.status_code = tracing::field::Empty,
+                http.response_size = tracing::field::Empty,
+                rows = tracing::field::Empty,
+                attempts = tracing::field::Empty,
+                duration_ms = tracing::field::Empty,
+                error = tracing::field::Empty,
+            ),
+        }
+    }
+
+    /// Runs a future inside the span.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        tracing::Instrument::instrument(future, self.span.clone())
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> F {
+        future
+    }
+
+    /// Runs a closure inside the span.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "tracing")]
+        let _enter = self.span.enter();
+        f()
+    }
+
+    /// Records that the call failed on an endpoint and is retried on the next one.
+    pub(crate) fn record_retry(&self, error: &Error) {
+        self.stats.lock().unwrap().attempts += 1;
+        for observer in &self.observers.0 {
+            observer.on_retry(&self.request, error);
+        }
+    }
+
+    pub(crate) fn record_response(&self, status_code: StatusCode, size: usize) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.status_code = Some(status_code);
+        stats.bytes_received = size;
+    }
+
+    pub(crate) fn finish<T: Rows>(&self, result: std::result::Result<&T, &Error>) {
+        let mut stats = self.stats.lock().unwrap();
+        if let Err(Error::Api { status_code, .. }) = result {
+            stats.status_code = Some(*status_code);
+        }
+        let outcome = RequestOutcome {
+            status_code: stats.status_code,
+            bytes_received: stats.bytes_received,
+            rows: result.ok().and_then(Rows::rows),
+            attempts: stats.attempts,
+            duration: self.start.elapsed(),
+            error: result.err(),
+        };
+        drop(stats);
+        #[cfg(feature = "tracing")]
+        {
+            if let Some(status_code) = outcome.status_code {
+                self.span.record("http.status_code", status_code.as_u16());
+            }
+            self.span
+                .record("http.response_size", outcome.bytes_received);
+            if let Some(rows) = outcome.rows {
+                self.span.record("rows", rows);
+            }
+            self.span.record("attempts", outcome.attempts);
+            self.span
+                .record("duration_ms", outcome.duration.as_millis() as u64);
+            if let Some(err) = outcome.error {
+                self.span.record("error", tracing::field::display(err));
+            }
+        }
+        for observer in &self.observers.0 {
+            observer.on_request_finish(&self.request, &outcome);
+        }
+    }
+}
+
+/// Returns the W3C trace context headers (`traceparent` and `tracestate`) of
+/// the current OpenTelemetry context, so that the server joins the trace.
+#[cfg(feature = "opentelemetry")]
+pub(crate) fn trace_context() -> HeaderMap {
+    use opentelemetry::trace::{TraceContextExt, TraceFlags};
+
+    let mut headers = HeaderMap::new();
+    let context = opentelemetry::Context::current();
+    let span = context.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() {
+        return headers;
+    }
+    let traceparent = format!(
+        "00-{}-{}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        span_context.trace_flags() & TraceFlags::SAMPLED
+    );
+    if let Ok(value) = traceparent.parse() {
+        headers.insert("traceparent", value);
+    }
+    let tracestate = span_context.trace_state().header();
+    if !tracestate.is_empty() {
+        if let Ok(value) = tracestate.parse() {
+            headers.insert("tracestate", value);
+        }
+    }
+    headers
+}
+
+#[cfg(not(feature = "opentelemetry"))]
+pub(crate) fn trace_context() -> HeaderMap {
+    HeaderMap::new()
+}
+
+#[cfg(all(test, feature = "opentelemetry"))]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+    };
+    use opentelemetry::Context;
+
+    #[test]
+    fn test_trace_context() {
+        assert!(trace_context().is_empty());
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::from_key_value([("gorse", "1")]).unwrap(),
+        );
+        let _guard = Context::new()
+            .with_remote_span_context(span_context)
+            .attach();
+        let headers = trace_context();
+        assert_eq!(
+            headers["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(headers["tracestate"], "gorse=1");
+    }
+}
@@ -0,0 +1,80 @@
+//! Fake HTTP server of the tests, answering each request with a canned
+//! response.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+/// Serves `requests` HTTP requests with `handler`, called with the head,
+/// in lower case, and the body of each request and returning the whole
+/// response, e.g. from [`json`]. Returns the entry point and a handle
+/// joining to the request lines received.
+pub(crate) fn serve(
+    requests: usize,
+    mut handler: impl FnMut(&str, &[u8]) -> String + Send + 'static,
+) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let entry_point = format!("http://{}/", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        for stream in listener.incoming().take(requests) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                head.push_str(&line.to_ascii_lowercase());
+            }
+            let mut body = Vec::new();
+            if head.contains("transfer-encoding: chunked") {
+                loop {
+                    let mut size = String::new();
+                    reader.read_line(&mut size).unwrap();
+                    let size = usize::from_str_radix(size.trim(), 16).unwrap();
+                    let mut chunk = vec![0; size + 2];
+                    reader.read_exact(&mut chunk).unwrap();
+                    if size == 0 {
+                        break;
+                    }
+                    body.extend_from_slice(&chunk[..size]);
+                }
+            } else if let Some(length) = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+            {
+                body.resize(length.trim().parse().unwrap(), 0);
+                reader.read_exact(&mut body).unwrap();
+            }
+            let response = handler(&head, &body);
+            received.push(head.lines().next().unwrap_or_default().to_string());
+            // The client may have given up, e.g. on a timeout.
+            let _ = stream.write_all(
+                response
+                    .replacen("\r\n\r\n", "\r\nConnection: close\r\n\r\n", 1)
+                    .as_bytes(),
+            );
+        }
+        received
+    });
+    (entry_point, handle)
+}
+
+/// Response of a status, e.g. `503 Service Unavailable`, with a body.
+pub(crate) fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// `200 OK` response with a JSON body.
+pub(crate) fn json(body: &str) -> String {
+    response("200 OK", "application/json", body)
+}
@@ -0,0 +1,141 @@
+//! RFC 3339 timestamps of users, items and feedback, without a date and
+//! time dependency.
+
+/// Formats a Unix timestamp in seconds in RFC 3339, e.g.
+/// `1997-12-04T15:55:49Z`.
+#[cfg(feature = "datasets")]
+pub(crate) fn format(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let seconds = seconds.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Parses an RFC 3339 timestamp, e.g. `2022-01-01T08:00:00.5+08:00`, or a
+/// date, e.g. `2022-01-01`, into Unix seconds and nanoseconds.
+pub(crate) fn parse(timestamp: &str) -> Option<(i64, u32)> {
+    let timestamp = timestamp.trim();
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = timestamp.get(range)?;
+        digits
+            .bytes()
+            .all(|byte| byte.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+    let separator = |index: usize, separators: &[u8]| {
+        timestamp
+            .as_bytes()
+            .get(index)
+            .is_some_and(|byte| separators.contains(byte))
+    };
+    if !separator(4, b"-") || !separator(7, b"-") {
+        return None;
+    }
+    let (month, day) = (number(5..7)?, number(8..10)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(number(0..4)?, month, day);
+    if timestamp.len() == 10 {
+        return Some((days * 86400, 0));
+    }
+    if !separator(10, b"Tt ") || !separator(13, b":") || !separator(16, b":") {
+        return None;
+    }
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut rest = &timestamp[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return None;
+        }
+        // Keep nanoseconds and drop finer digits.
+        let digits = &fraction[..len.min(9)];
+        nanos = digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32);
+        rest = &fraction[len..];
+    }
+    let offset = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), h1, h2, b':', m1, m2] => {
+            let digits = [*h1, *h2, *m1, *m2];
+            if !digits.iter().all(u8::is_ascii_digit) {
+                return None;
+            }
+            let [h1, h2, m1, m2] = digits.map(|digit| i64::from(digit - b'0'));
+            let offset = (h1 * 10 + h2) * 3600 + (m1 * 10 + m2) * 60;
+            if *sign == b'+' {
+                offset
+            } else {
+                -offset
+            }
+        }
+        _ => return None,
+    };
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+    Some((seconds, nanos))
+}
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Date of the proleptic Gregorian calendar of days since 1970-01-01.
+#[cfg(feature = "datasets")]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "datasets")]
+    #[test]
+    fn test_format() {
+        assert_eq!(format(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format(881250949), "1997-12-04T15:55:49Z");
+        assert_eq!(format(-1), "1969-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("1997-12-04T15:55:49Z"), Some((881250949, 0)));
+        assert_eq!(
+            parse("1997-12-04 23:55:49.25+08:00"),
+            Some((881250949, 250_000_000))
+        );
+        assert_eq!(parse("1970-01-01"), Some((0, 0)));
+        assert_eq!(parse("1969-12-31T23:00:00-01:00"), Some((0, 0)));
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("1997-12-04T15:55:49"), None);
+        assert_eq!(parse("1997-13-04T15:55:49Z"), None);
+        assert_eq!(parse("+997-12-04"), None);
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::Error;
+
+/// Version of a Gorse server, e.g. `0.5.0`. Only the numeric part of a
+/// version is kept, so `v0.5.1-rc1` is `0.5.1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServerVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl ServerVersion {
+    /// First version serving named recommenders, e.g.
+    /// [`Gorse::get_item_to_item`](crate::Gorse::get_item_to_item).
+    pub const NAMED_RECOMMENDERS: ServerVersion = ServerVersion::new(0, 5, 0);
+    /// First version returning scores from
+    /// [`Gorse::get_recommend`](crate::Gorse::get_recommend).
+    pub const SCORED_RECOMMEND: ServerVersion = ServerVersion::new(0, 5, 0);
+
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Whether the server serves the named recommender routes rather than
+    /// the legacy neighbors and intermediate recommendation routes.
+    pub fn has_named_recommenders(&self) -> bool {
+        *self >= Self::NAMED_RECOMMENDERS
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for ServerVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::Config(format!("invalid server version: {}", s));
+        let version = s.trim();
+        let version = version.strip_prefix('v').unwrap_or(version);
+        let version = version.split(['-', '+']).next().unwrap_or_default();
+        let mut parts = version.split('.').map(|part| part.parse::<u64>());
+        let major = parts.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+        let minor = parts.next().transpose().map_err(|_| invalid())?;
+        let patch = parts.next().transpose().map_err(|_| invalid())?;
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self::new(major, minor.unwrap_or(0), patch.unwrap_or(0)))
+    }
+}
+
+impl<'de> Deserialize<'de> for ServerVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Version and capabilities of a Gorse server, from
+/// [`Gorse::server_info`](crate::Gorse::server_info).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ServerInfo {
+    /// Version of the server, `None` if it does not report it, e.g. servers
+    /// older than 0.5.
+    #[serde(rename = "Version")]
+    pub version: Option<ServerVersion>,
+    /// Whether a server that does not report its version returned scores
+    /// from the recommendation route, like servers since 0.5.
+    #[serde(skip)]
+    scored: bool,
+}
+
+impl ServerInfo {
+    pub(crate) fn new(version: ServerVersion) -> Self {
+        Self {
+            version: Some(version),
+            scored: false,
+        }
+    }
+
+    /// Information of a server that does not report its version, with the
+    /// capabilities of servers since 0.5 if it returns scored
+    /// recommendations.
+    pub(crate) fn unreported(scored: bool) -> Self {
+        Self {
+            version: None,
+            scored,
+        }
+    }
+
+    /// Information of a server older than 0.5.
+    pub(crate) fn legacy() -> Self {
+        Self::unreported(false)
+    }
+
+    /// Whether the server serves named recommenders.
+    pub fn has_named_recommenders(&self) -> bool {
+        self.version
+            .map_or(self.scored, |version| version.has_named_recommenders())
+    }
+
+    /// Whether the server returns scores with recommended items rather than
+    /// item ids only.
+    pub fn has_scored_recommend(&self) -> bool {
+        self.version.map_or(self.scored, |version| {
+            version >= ServerVersion::SCORED_RECOMMEND
+        })
+    }
+
+    pub(crate) fn unsupported(&self, feature: impl fmt::Display) -> Error {
+        Error::Unsupported(match self.version {
+            Some(version) => format!("{} is not supported by Gorse {}", feature, version),
+            None => format!("{} is not supported by Gorse servers before 0.5", feature),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> crate::Result<()> {
+        assert_eq!(
+            "0.5.1".parse::<ServerVersion>()?,
+            ServerVersion::new(0, 5, 1)
+        );
+        assert_eq!(
+            "v0.4.15-rc1".parse::<ServerVersion>()?,
+            ServerVersion::new(0, 4, 15)
+        );
+        assert_eq!("1.2".parse::<ServerVersion>()?, ServerVersion::new(1, 2, 0));
+        assert!("".parse::<ServerVersion>().is_err());
+        assert!("0.x".parse::<ServerVersion>().is_err());
+        assert!("1.2.3.4".parse::<ServerVersion>().is_err());
+
+        assert!(!ServerVersion::new(0, 4, 15).has_named_recommenders());
+        assert!(ServerVersion::new(0, 5, 0).has_named_recommenders());
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_info() -> crate::Result<()> {
+        let info: ServerInfo = serde_json::from_str(r#"{"Version":"v0.5.2"}"#)?;
+        assert_eq!(info.version, Some(ServerVersion::new(0, 5, 2)));
+        assert!(info.has_named_recommenders());
+        assert!(info.has_scored_recommend());
+
+        let legacy = ServerInfo::legacy();
+        assert!(!legacy.has_named_recommenders());
+        assert!(!legacy.has_scored_recommend());
+        assert!(matches!(legacy.unsupported("x"), Error::Unsupported(_)));
+
+        let unreported = ServerInfo::unreported(true);
+        assert!(unreported.has_named_recommenders());
+        assert!(unreported.has_scored_recommend());
+        Ok(())
+    }
+}